@@ -1,14 +1,30 @@
-use std::ffi::OsString;
-use std::fs::{self, Metadata};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::{self, DirEntry, Metadata};
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
 use anyhow::{bail, format_err};
-use colored::Colorize;
+use chrono::{DateTime, Local};
+use filetime::FileTime;
+use regex::Regex;
+use twox_hash::XxHash3_64;
 
-use crate::config::Config;
+#[cfg(test)]
+use crate::checker::test_fixture;
+use crate::config::{self, Config};
 use crate::rules::{self, FileMatchRule};
 
+/// Output of a to-script meaning the file is left in place
+pub const SCRIPT_SKIP: &str = "SKIP";
+
 #[derive(Debug, Clone)]
 pub struct AutoMove {
     pub parent: Option<PathBuf>,
@@ -17,19 +33,67 @@ pub struct AutoMove {
 
 /// A rule to move files
 #[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct AutoMoveRule {
     /// Custom display name of the rule
     pub custom_name: Option<String>,
     /// Directory in which files will be checked
     pub directory: PathBuf,
+    /// Whether `directory` is one of the matches of a glob `parent`
+    pub from_glob: bool,
     /// Matching rules to determine which files need to be moved
     pub match_rules: FileMatchRule,
     /// Target directory in which files will be put
     pub to: PathBuf,
+    /// Target directories by extension, used instead of `to`. Files matching none of
+    /// the extensions are not moved
+    pub route: Vec<(FileMatchRule, PathBuf)>,
+    /// Whether `to` and `route` are relative to the directory of each file, rather than
+    /// directories files of every subdirectory are gathered in
+    pub to_relative: bool,
+    /// Subdirectory for files without an extension, if files are grouped in
+    /// subdirectories named after their (lowercase) extension
+    pub group_by_extension: Option<OsString>,
     /// Custom script path to give a new filename to files.
     ///
     /// It can also return the new absolute path.
     pub to_script: Option<PathBuf>,
+    /// Whether `to_script` is called once for all files rather than once per file
+    pub to_script_batch: bool,
+    /// Exit code of `to_script` meaning files are left in place. They also are when it
+    /// prints nothing or [`SCRIPT_SKIP`]
+    pub to_script_skip_code: Option<i32>,
+    /// Time after which `to_script` is killed
+    pub script_timeout: Option<Duration>,
+    /// Regex based renaming, files that don't match it are not moved
+    pub rename: Option<Rename>,
+    /// Whether to move files from subdirectories too. Directories themselves are never moved then
+    pub recursive: bool,
+    /// Subdirectories to ignore when `recursive` is `true`
+    pub recursive_ignore_rules: FileMatchRule,
+    /// Whether matching directories are moved along with their contents, otherwise they are
+    /// never candidates (symlinks to directories are, as only the link is moved)
+    pub move_directories: bool,
+    /// Existing destinations that may be overwritten
+    pub overwrite_if: FileMatchRule,
+}
+
+/// A file to be moved by a rule
+#[derive(Debug)]
+struct Candidate {
+    dir_entry: DirEntry,
+    metadata: Metadata,
+    /// Path of the directory containing the file, relative to the rule's directory
+    subpath: PathBuf,
+}
+
+/// Renames files from a regex, whose capture groups can be used in the new name
+#[derive(Debug, Clone)]
+pub struct Rename {
+    /// Regex the filename needs to match
+    pub from: Regex,
+    /// New filename, with `$1`, `${name}`, etc. replaced by capture groups
+    pub to_name: String,
 }
 
 /// Result from attempting to execute a rule
@@ -41,6 +105,10 @@ pub enum AutoMoveResult<'a> {
     Ok {
         rule: &'a AutoMoveRule,
         entries: Vec<Result<AutoMoveResultEntry, anyhow::Error>>,
+        /// How many directories were (or would be) removed after being emptied
+        removed_dirs: usize,
+        /// Entries that couldn't be read, and were therefore not considered for a move
+        errors: Vec<(PathBuf, io::Error)>,
     },
 }
 
@@ -53,6 +121,82 @@ pub struct AutoMoveResultEntry {
     pub file_metadata: Metadata,
     /// New file path to be moved to
     pub move_to: PathBuf,
+    /// How a file already at `move_to` was (or would be) dealt with
+    pub resolution: Option<ConflictResolution>,
+    /// Whether the move was declined in interactive mode, or cancelled by a failed transaction
+    pub declined: bool,
+}
+
+/// An error about a file to move, keeping the paths it is about
+#[derive(Debug)]
+pub struct EntryError {
+    /// Path of the file that wasn't moved
+    pub file: PathBuf,
+    /// Path the file was to be moved to, if it was known
+    pub move_to: Option<PathBuf>,
+    error: anyhow::Error,
+}
+
+impl EntryError {
+    /// Attaches the paths of a file to an error, which still displays as the original one
+    pub fn wrap(error: anyhow::Error, file: PathBuf, move_to: Option<PathBuf>) -> anyhow::Error {
+        anyhow::Error::new(Self {
+            file,
+            move_to,
+            error,
+        })
+    }
+}
+
+impl fmt::Display for EntryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl std::error::Error for EntryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// Outcome of a conflict with a file already at the destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// The existing file was sent to the trash
+    Trashed,
+    /// The existing file was overwritten as it was older
+    ReplacedOlder,
+    /// The file was left in place as the existing one is at least as recent
+    KeptNewer,
+    /// The file was identical to the existing one and was deleted
+    RemovedIdentical,
+    /// The file was identical to the existing one and was left in place
+    KeptIdentical,
+}
+
+impl AutoMoveResultEntry {
+    /// Whether the file was left in place because of a conflict, or declined
+    pub fn is_skipped(&self) -> bool {
+        self.declined
+            || matches!(
+                self.resolution,
+                Some(
+                    ConflictResolution::KeptNewer
+                        | ConflictResolution::RemovedIdentical
+                        | ConflictResolution::KeptIdentical
+                )
+            )
+    }
+
+    /// Whether the file is no longer at its original path, moved or deleted
+    pub fn is_source_gone(&self) -> bool {
+        !self.declined
+            && !matches!(
+                self.resolution,
+                Some(ConflictResolution::KeptNewer | ConflictResolution::KeptIdentical)
+            )
+    }
 }
 
 impl AutoMove {
@@ -60,7 +204,7 @@ impl AutoMove {
     ///
     /// This doesn't actually move the files but each entry contains the
     /// current file path and the new wanted file path.
-    pub fn run(&self) -> Vec<AutoMoveResult> {
+    pub fn run(&self) -> Vec<AutoMoveResult<'_>> {
         self.rules
             .iter()
             .filter(|rule| {
@@ -84,155 +228,1011 @@ impl AutoMove {
         self.rules.iter().fold(0, |a, b| a + b.count_move())
     }
 
-    pub fn check_empty(&self, config: &Config) -> anyhow::Result<()> {
-        if self.rules.is_empty() {
-            let w = if config.settings.color {
-                "(!)".bold()
-            } else {
-                "(!)".into()
-            };
-            bail!("{} No auto-move rules were configured.", w)
+    /// Ensures the to-scripts of all rules exist and can be executed, so that a typo
+    /// fails the run before any file is moved rather than for each file
+    pub fn check_scripts(&self) -> anyhow::Result<()> {
+        for rule in &self.rules {
+            if let Some(to_script) = &rule.to_script {
+                check_script(to_script).map_err(|err| {
+                    format_err!("Auto-move rule '{}': {err}", rule.display_name())
+                })?;
+            }
         }
         Ok(())
     }
+}
 
-    ///Warn user about slow execution time
-    pub fn script_warning(&self, config: &Config) -> bool {
-        let script_warning = config.automove.script_warning
-            && self.rules.iter().any(|rule| rule.to_script.is_some());
-        if script_warning {
-            // print on stderr to not affect pipe input (e.g. when using --list)
-            if config.settings.color {
-                eprintln!("{} Your auto-move rules are configured to call scripts {}. If execution time gets too long, {}.", "Heads up!".bright_red().bold(), "(to-script)".white().dimmed(), "scripts are the cause".bold());
-            } else {
-                eprintln!("Heads up! Your auto-move rules are configured to call scripts (to-script). If execution time gets too long, scripts are the cause.");
+impl Rename {
+    /// Returns the new filename, or the same name if it doesn't match
+    pub fn apply(&self, filename: &str) -> String {
+        match self.from.captures(filename) {
+            Some(captures) => {
+                let mut new_name = String::new();
+                captures.expand(&self.to_name, &mut new_name);
+                new_name
             }
+            None => filename.to_string(),
         }
-        script_warning
     }
 }
 
 impl AutoMoveRule {
-    /// Returns the display name of the rule (could be a custom name or path str representation)
-    pub fn display_name(&self) -> String {
+    /// Whether the existing file at `destination` matches `overwrite-if`, allowing it to be
+    /// overwritten
+    pub fn may_overwrite(&self, destination: &Path) -> anyhow::Result<bool> {
+        let (Some(dir), Some(filename)) = (destination.parent(), destination.file_name()) else {
+            return Ok(false);
+        };
+        for dir_entry in fs::read_dir(dir)? {
+            let dir_entry = dir_entry?;
+            if dir_entry.file_name() == filename {
+                return self
+                    .overwrite_if
+                    .matches_dir_entry(&dir_entry, &self.to, true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Checks again, right before moving them, that files are unchanged since the rule ran
+    /// and still match it. Returns whether each entry may still be moved.
+    pub fn recheck(&self, entries: &[&AutoMoveResultEntry]) -> Vec<bool> {
+        let dirs = entries
+            .iter()
+            .filter_map(|entry| entry.file.parent())
+            .collect::<HashSet<_>>();
+        let mut dir_entries = HashMap::new();
+        for dir in dirs {
+            let Ok(read_dir) = fs::read_dir(dir) else {
+                continue;
+            };
+            for dir_entry in read_dir.flatten() {
+                dir_entries.insert(dir_entry.path(), dir_entry);
+            }
+        }
+        entries
+            .iter()
+            .map(|entry| {
+                let unmodified = fs::symlink_metadata(&entry.file)
+                    .is_ok_and(|metadata| is_unmodified(&entry.file_metadata, &metadata));
+                unmodified
+                    && dir_entries
+                        .get(&entry.file)
+                        .is_some_and(|dir_entry| self.is_candidate(dir_entry))
+            })
+            .collect()
+    }
+
+    /// Whether `to` (or a `route` directory) is a subdirectory of the rule's directory
+    pub fn has_nested_destination(&self) -> bool {
+        self.destination_dirs()
+            .any(|to| *to != self.directory && to.starts_with(&self.directory))
+    }
+
+    /// Whether some rules compare access times, but the directory is on a filesystem that
+    /// doesn't record them
+    pub fn access_times_unrecorded(&self) -> bool {
+        (self.match_rules.uses_access_time() || self.recursive_ignore_rules.uses_access_time())
+            && !rules::records_access_times(&self.directory)
+    }
+
+    /// Returns the directories files can be moved to
+    fn destination_dirs(&self) -> impl Iterator<Item = &PathBuf> {
+        let to = self.route.is_empty().then_some(&self.to);
+        to.into_iter().chain(self.route.iter().map(|(_, to)| to))
+    }
+
+    /// Returns the directory an entry goes to, `None` if no route applies to it
+    fn destination_dir(&self, dir_entry: &DirEntry) -> Option<&Path> {
+        if self.route.is_empty() {
+            return Some(&self.to);
+        }
+        self.route
+            .iter()
+            .find(|(ext, _)| {
+                ext.matches_dir_entry(dir_entry, &self.directory, true)
+                    .unwrap_or(false)
+            })
+            .map(|(_, to)| to.as_path())
+    }
+
+    /// Returns the name of the rule (could be a custom name or path str representation)
+    pub fn name(&self) -> String {
         self.custom_name
             .clone()
             .unwrap_or_else(|| self.directory.to_string_lossy().to_string())
     }
 
+    /// Returns the display name of the rule, which includes the directory
+    /// when the rule was expanded from a glob
+    pub fn display_name(&self) -> String {
+        match &self.custom_name {
+            Some(name) if self.from_glob => {
+                format!("{name} ({})", self.directory.to_string_lossy())
+            }
+            _ => self.name(),
+        }
+    }
+
     /// Returns entries that should be moved if it didn't encounter any error
-    pub fn run(&self) -> AutoMoveResult {
-        self.run_on_path(&self.directory)
+    pub fn run(&self) -> AutoMoveResult<'_> {
+        let mut read_errors = Vec::new();
+        let Some(candidates) = self.candidates(&mut read_errors) else {
+            return AutoMoveResult::DirDoesNotExist { rule: self };
+        };
+        let (destinations, errors) = match &self.to_script {
+            Some(to_script) if self.to_script_batch => {
+                self.batch_destinations(to_script, &candidates)
+            }
+            _ => (
+                parallel_map(&candidates, |candidate| self.destination(candidate)),
+                Vec::new(),
+            ),
+        };
+        let entries = candidates
+            .into_iter()
+            .zip(destinations)
+            .filter_map(|(candidate, destination)| {
+                let move_to = match destination {
+                    Ok(Some(move_to)) => move_to,
+                    Ok(None) => {
+                        tracing::debug!(
+                            "{}: skipped by to-script",
+                            candidate.dir_entry.path().to_string_lossy()
+                        );
+                        return None;
+                    }
+                    Err(err) => {
+                        let file = candidate.dir_entry.path();
+                        return Some(Err(EntryError::wrap(err, file, None)));
+                    }
+                };
+                Some(Ok(AutoMoveResultEntry {
+                    file: candidate.dir_entry.path(),
+                    file_metadata: candidate.metadata,
+                    move_to,
+                    resolution: None,
+                    declined: false,
+                }))
+            })
+            .chain(errors.into_iter().map(Err))
+            .collect();
+        AutoMoveResult::Ok {
+            rule: self,
+            entries,
+            removed_dirs: 0,
+            errors: read_errors,
+        }
     }
 
     /// Checks if any file would be moved without getting a full list of entries
     pub fn would_move(&self) -> bool {
-        let result = self.count_matches_on_path(&self.directory, true);
-        result >= 1
+        self.count_move() >= 1
     }
 
     /// Counts how many files would be moved without getting a full list of entries
     pub fn count_move(&self) -> usize {
-        self.count_matches_on_path(&self.directory, false)
+        self.candidates(&mut Vec::new())
+            .map_or(0, |candidates| candidates.len())
+    }
+
+    /// Checks if a directory entry should be moved by this rule
+    fn is_candidate(&self, dir_entry: &DirEntry) -> bool {
+        let path = dir_entry.path();
+        if !self.move_directories && dir_entry.file_type().is_ok_and(|ft| ft.is_dir()) {
+            tracing::debug!("{}: is a directory", path.to_string_lossy());
+            return false;
+        }
+        if !self
+            .match_rules
+            .matches_dir_entry(dir_entry, &self.directory, true)
+            .ok()
+            .unwrap_or(false)
+        {
+            tracing::debug!("{}: doesn't match", path.to_string_lossy());
+            return false;
+        }
+        if self.destination_dir(dir_entry).is_none() {
+            tracing::debug!("{}: no route applies", path.to_string_lossy());
+            return false;
+        }
+        if let Some(rename) = &self.rename {
+            if !rename
+                .from
+                .is_match(&dir_entry.file_name().to_string_lossy())
+            {
+                tracing::debug!("{}: rename pattern doesn't match", path.to_string_lossy());
+                return false;
+            }
+        }
+        tracing::debug!("{}: will be moved", path.to_string_lossy());
+        true
     }
 
-    fn count_matches_on_path(&self, path: &Path, exit_on_first: bool) -> usize {
-        let dir_entries = match fs::read_dir(path) {
-            Ok(entries) => entries,
-            Err(_) => return 0,
+    /// Computes the path a matching entry should be moved to, `None` if the to-script
+    /// skips it
+    fn destination(&self, candidate: &Candidate) -> anyhow::Result<Option<PathBuf>> {
+        let dir_entry = &candidate.dir_entry;
+        let mut output_filename = dir_entry.file_name();
+        if let Some(rename) = &self.rename {
+            output_filename = rename
+                .apply(&dir_entry.file_name().to_string_lossy())
+                .into();
+        }
+        if let Some(to_script) = &self.to_script {
+            let output = output_with_timeout(
+                &mut self.script_command(to_script, candidate),
+                None,
+                self.script_timeout,
+            )
+            .map_err(|err| {
+                format_err!(
+                    "Could not execute to-script for '{}': {}: {}",
+                    dir_entry.file_name().to_string_lossy(),
+                    to_script.to_string_lossy(),
+                    err
+                )
+            })?;
+            if self.is_script_skip(&output) {
+                return Ok(None);
+            }
+            if let Some(failure) = script_failure(&output) {
+                bail!(
+                    "to-script failed for '{}': {}",
+                    dir_entry.file_name().to_string_lossy(),
+                    failure
+                );
+            }
+            output_filename = command_output_to_filename(&output.stdout);
+            if output_filename.is_empty() || output_filename == SCRIPT_SKIP {
+                return Ok(None);
+            }
+        }
+        self.place(candidate, output_filename).map(Some)
+    }
+
+    /// Whether a to-script exited with `to_script_skip_code`
+    fn is_script_skip(&self, output: &Output) -> bool {
+        self.to_script_skip_code
+            .is_some_and(|code| output.status.code() == Some(code))
+    }
+
+    /// Builds the path to move a file to from its new filename, which may also be an absolute path
+    fn place(&self, candidate: &Candidate, output_filename: OsString) -> anyhow::Result<PathBuf> {
+        let move_to = if Path::new(&output_filename).is_absolute() {
+            PathBuf::from(output_filename)
+        } else {
+            let to = self
+                .destination_dir(&candidate.dir_entry)
+                .unwrap_or(&self.to);
+            let to = expand_to_template(to, &candidate.dir_entry, &candidate.metadata)?;
+            let mut buf = if self.to_relative {
+                self.directory.join(&candidate.subpath).join(to)
+            } else {
+                to
+            };
+            if let Some(no_extension_dir) = &self.group_by_extension {
+                buf.push(extension_dir(&output_filename, no_extension_dir));
+            }
+            if !self.to_relative {
+                buf.push(&candidate.subpath);
+            }
+            buf.push(output_filename);
+            buf
         };
+        Ok(move_to)
+    }
 
-        let mut count = 0;
-        for dir_entry in dir_entries {
-            if dir_entry.is_err() {
+    /// Computes the destinations of all candidates with a single to-script call.
+    ///
+    /// The script gets one path per line on stdin and must print `old path<TAB>new path`
+    /// lines. Output lines that can't be matched to a candidate are returned as extra errors.
+    fn batch_destinations(
+        &self,
+        to_script: &Path,
+        candidates: &[Candidate],
+    ) -> (Vec<anyhow::Result<Option<PathBuf>>>, Vec<anyhow::Error>) {
+        let mut input = String::new();
+        for candidate in candidates {
+            input.push_str(&candidate.dir_entry.path().to_string_lossy());
+            input.push('\n');
+        }
+        let mut command = Command::new(to_script);
+        for var in [
+            "SHINYDIR_FILE_SIZE",
+            "SHINYDIR_FILE_MTIME",
+            "SHINYDIR_FILE_EXT",
+        ] {
+            command.env_remove(var);
+        }
+        command.env("SHINYDIR_RULE_NAME", self.name());
+        let output = match output_with_timeout(
+            &mut command,
+            Some(input.into_bytes()),
+            self.script_timeout,
+        ) {
+            Ok(output) => output,
+            Err(err) => {
+                let failure = format!(
+                    "Could not execute to-script: {}: {}",
+                    to_script.to_string_lossy(),
+                    err
+                );
+                return (fail_all(candidates, &failure), Vec::new());
+            }
+        };
+        if self.is_script_skip(&output) {
+            return (candidates.iter().map(|_| Ok(None)).collect(), Vec::new());
+        }
+        if let Some(failure) = script_failure(&output) {
+            let failure = format!("to-script failed: {failure}");
+            return (fail_all(candidates, &failure), Vec::new());
+        }
+
+        let mut new_paths = HashMap::new();
+        let mut errors = Vec::new();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        for (i, line) in stdout.lines().enumerate() {
+            if line.trim().is_empty() {
                 continue;
             }
-            let dir_entry = dir_entry.unwrap();
-            if !self
-                .match_rules
-                .matches_dir_entry(&dir_entry)
-                .ok()
-                .unwrap_or(false)
+            let Some((old_path, new_path)) = line.split_once('\t') else {
+                errors.push(format_err!(
+                    "to-script output line {} is missing a tab separator: {line}",
+                    i + 1
+                ));
+                continue;
+            };
+            if !candidates
+                .iter()
+                .any(|candidate| candidate.dir_entry.path() == Path::new(old_path))
             {
+                errors.push(format_err!(
+                    "to-script returned a destination for an unexpected file: {old_path}"
+                ));
                 continue;
             }
-            if dir_entry.metadata().is_ok() {
-                count += 1;
-            }
-            if exit_on_first {
-                break;
+            if new_paths
+                .insert(PathBuf::from(old_path), new_path.trim())
+                .is_some()
+            {
+                errors.push(format_err!(
+                    "to-script returned several destinations for {old_path}"
+                ));
             }
         }
-        count
+
+        let destinations = candidates
+            .iter()
+            .map(
+                |candidate| match new_paths.get(&candidate.dir_entry.path()) {
+                    Some(&"" | &SCRIPT_SKIP) => Ok(None),
+                    Some(new_path) => self.place(candidate, OsString::from(new_path)).map(Some),
+                    None => Err(format_err!(
+                        "to-script returned no destination for '{}'",
+                        candidate.dir_entry.file_name().to_string_lossy()
+                    )),
+                },
+            )
+            .collect();
+        (destinations, errors)
     }
 
-    fn run_on_path(&self, path: &Path) -> AutoMoveResult {
-        let dir_entries = match fs::read_dir(path) {
-            Ok(entries) => entries,
-            Err(_) => return AutoMoveResult::DirDoesNotExist { rule: self },
+    /// Prepares a to-script call for a file, passing its metadata through `SHINYDIR_*` variables.
+    ///
+    /// Every variable is always set (or removed) so values inherited from
+    /// the environment never leak into the script.
+    fn script_command(&self, to_script: &Path, candidate: &Candidate) -> Command {
+        let mut command = Command::new(to_script);
+        command.arg(candidate.dir_entry.path().to_string_lossy().as_ref());
+        command.env("SHINYDIR_FILE_SIZE", candidate.metadata.len().to_string());
+        match candidate
+            .metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        {
+            Some(mtime) => command.env("SHINYDIR_FILE_MTIME", mtime.as_secs().to_string()),
+            None => command.env_remove("SHINYDIR_FILE_MTIME"),
         };
+        command.env(
+            "SHINYDIR_FILE_EXT",
+            Path::new(&candidate.dir_entry.file_name())
+                .extension()
+                .unwrap_or_default(),
+        );
+        command.env("SHINYDIR_RULE_NAME", self.name());
+        command
+    }
+
+    /// Lists the files to be moved, or `None` if the directory can't be read.
+    ///
+    /// Entries that couldn't be read are pushed to `errors`.
+    fn candidates(&self, errors: &mut Vec<(PathBuf, io::Error)>) -> Option<Vec<Candidate>> {
+        let mut candidates = Vec::new();
+        self.collect_candidates(
+            &self.directory,
+            Path::new(""),
+            &mut HashSet::new(),
+            &mut candidates,
+            errors,
+        )
+        .ok()?;
+        Some(candidates)
+    }
+
+    /// Collects candidates from a directory, descending into subdirectories when recursive.
+    ///
+    /// Entries that can't be read are pushed to `errors`, the error is only
+    /// returned if the directory itself can't be read.
+    fn collect_candidates(
+        &self,
+        path: &Path,
+        subpath: &Path,
+        visited: &mut HashSet<PathBuf>,
+        candidates: &mut Vec<Candidate>,
+        errors: &mut Vec<(PathBuf, io::Error)>,
+    ) -> io::Result<()> {
+        let dir_entries = fs::read_dir(path)?;
+        if let Ok(canonical_path) = fs::canonicalize(path) {
+            if !visited.insert(canonical_path) {
+                return Ok(());
+            }
+        }
 
-        let mut result_entries = Vec::new();
         for dir_entry in dir_entries {
-            if dir_entry.is_err() {
+            let dir_entry = match dir_entry {
+                Ok(dir_entry) => dir_entry,
+                Err(err) => {
+                    errors.push((path.to_path_buf(), err));
+                    continue;
+                }
+            };
+            let is_dir = dir_entry.file_type().is_ok_and(|ft| ft.is_dir());
+            if self.recursive && is_dir {
+                // never walk into the destination, files there are already sorted
+                let ignored = self.destination_dirs().any(|to| {
+                    if self.to_relative {
+                        path.join(to).starts_with(dir_entry.path())
+                    } else {
+                        dir_entry.path() == *to
+                    }
+                }) || self
+                    .recursive_ignore_rules
+                    .matches_dir_entry(&dir_entry, &self.directory, true)
+                    .unwrap_or(false);
+                if !ignored {
+                    if let Err(err) = self.collect_candidates(
+                        &dir_entry.path(),
+                        &subpath.join(dir_entry.file_name()),
+                        visited,
+                        candidates,
+                        errors,
+                    ) {
+                        errors.push((dir_entry.path(), err));
+                    }
+                }
                 continue;
             }
-            let dir_entry = dir_entry.unwrap();
-            if !self
-                .match_rules
-                .matches_dir_entry(&dir_entry)
-                .ok()
-                .unwrap_or(false)
-            {
+            if !self.is_candidate(&dir_entry) {
                 continue;
             }
-            if let Ok(file_metadata) = dir_entry.metadata() {
-                let mut output_filename = dir_entry.file_name();
-                if let Some(to_script) = &self.to_script {
-                    let output = Command::new(to_script)
-                        .arg(dir_entry.path().to_string_lossy().as_ref())
-                        .output();
-                    if let Err(err) = output {
-                        result_entries.push(Err(format_err!(
-                            "Could not execute to-script for '{}': {}: {}",
-                            dir_entry.file_name().to_string_lossy(),
-                            to_script.to_string_lossy(),
-                            err
-                        )));
-                        continue;
+            match dir_entry.metadata() {
+                Ok(metadata) => candidates.push(Candidate {
+                    dir_entry,
+                    metadata,
+                    subpath: subpath.to_path_buf(),
+                }),
+                Err(err) => errors.push((dir_entry.path(), err)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reports the same error for every candidate
+fn fail_all(candidates: &[Candidate], failure: &str) -> Vec<anyhow::Result<Option<PathBuf>>> {
+    candidates
+        .iter()
+        .map(|candidate| {
+            Err(format_err!(
+                "{failure} (for '{}')",
+                candidate.dir_entry.file_name().to_string_lossy()
+            ))
+        })
+        .collect()
+}
+
+/// Describes why a script failed from its exit status and stderr, if it did
+fn script_failure(output: &Output) -> Option<String> {
+    if output.status.success() {
+        return None;
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim();
+    if stderr.is_empty() {
+        Some(output.status.to_string())
+    } else {
+        Some(format!("{}: {stderr}", output.status))
+    }
+}
+
+/// Runs a command to completion like [`Command::output`], writing `input` to its stdin
+/// and killing it once `timeout` is reached
+fn output_with_timeout(
+    command: &mut Command,
+    input: Option<Vec<u8>>,
+    timeout: Option<Duration>,
+) -> io::Result<Output> {
+    let mut child = command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // pipes are fed and drained in the background so a chatty child can't block on a full pipe
+    if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+        thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        });
+    }
+    let read_pipe = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        })
+    };
+    let stdout = read_pipe(child.stdout.take().map(|pipe| Box::new(pipe) as _));
+    let stderr = read_pipe(child.stderr.take().map(|pipe| Box::new(pipe) as _));
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if let (Some(deadline), Some(timeout)) = (deadline, timeout) {
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("timed out after {}s", timeout.as_secs()),
+                ));
+            }
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Replaces `{year}`, `{month}`, `{day}` (from the file's modification time)
+/// and `{ext}` placeholders in a destination directory
+fn expand_to_template(
+    to: &Path,
+    dir_entry: &DirEntry,
+    metadata: &Metadata,
+) -> anyhow::Result<PathBuf> {
+    let template = to.to_string_lossy();
+    if !template.contains('{') {
+        return Ok(to.to_path_buf());
+    }
+    let mut expanded = template.to_string();
+    if ["{year}", "{month}", "{day}"]
+        .iter()
+        .any(|placeholder| expanded.contains(placeholder))
+    {
+        let modified: DateTime<Local> = metadata
+            .modified()
+            .map_err(|err| {
+                format_err!(
+                    "Could not read modification time of '{}': {}",
+                    dir_entry.file_name().to_string_lossy(),
+                    err
+                )
+            })?
+            .into();
+        expanded = expanded
+            .replace("{year}", &modified.format("%Y").to_string())
+            .replace("{month}", &modified.format("%m").to_string())
+            .replace("{day}", &modified.format("%d").to_string());
+    }
+    let ext = Path::new(&dir_entry.file_name())
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok(PathBuf::from(expanded.replace("{ext}", &ext)))
+}
+
+/// Expands a rule whose directory is a glob into one rule per matching directory.
+///
+/// A glob that matches nothing is kept as-is so it gets reported as a missing directory.
+pub fn expand_parent_glob(rule: AutoMoveRule) -> anyhow::Result<Vec<AutoMoveRule>> {
+    let pattern = rule.directory.to_string_lossy().to_string();
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(vec![rule]);
+    }
+    let directories = glob::glob(&pattern)
+        .map_err(|err| format_err!("Invalid parent pattern '{pattern}': {err}"))?
+        .flatten()
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    if directories.is_empty() {
+        return Ok(vec![rule]);
+    }
+    Ok(directories
+        .into_iter()
+        .map(|directory| AutoMoveRule {
+            directory,
+            from_glob: true,
+            ..rule.clone()
+        })
+        .collect())
+}
+
+/// Applies a function to every item with a pool of threads, keeping the order of results
+pub fn parallel_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let workers = thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(items.len());
+    if workers <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let mut results = thread::scope(|scope| {
+        let handles = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(i) else {
+                            break;
+                        };
+                        results.push((i, f(item)));
                     }
-                    let output = output.unwrap();
-                    output_filename = command_output_to_filename(&output.stdout);
-                }
+                    results
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("worker thread panicked"))
+            .collect::<Vec<_>>()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
 
-                let move_to = if Path::new(&output_filename).is_absolute() {
-                    PathBuf::from(output_filename)
-                } else {
-                    let mut buf = self.to.clone();
-                    buf.push(output_filename);
-                    buf
-                };
-                let entry = AutoMoveResultEntry {
-                    file: dir_entry.path(),
-                    file_metadata,
-                    move_to,
-                };
-                result_entries.push(Ok(entry));
+/// Whether metadata read at two points in time belong to the same, unmodified file
+fn is_unmodified(before: &Metadata, after: &Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let identity = |md: &Metadata| (md.dev(), md.ino(), md.ctime(), md.ctime_nsec());
+        if identity(before) != identity(after) {
+            return false;
+        }
+    }
+    before.file_type() == after.file_type()
+        && before.len() == after.len()
+        && before.modified().ok() == after.modified().ok()
+}
+
+/// Moves a file or a directory, falling back to copy-then-delete when the destination
+/// is on another filesystem.
+///
+/// Unless `replace` is set, the move fails with [`io::ErrorKind::AlreadyExists`] if there is
+/// a file at the destination, atomically where the platform allows it so that a file created
+/// there meanwhile isn't overwritten.
+///
+/// The source is only removed once the copy is complete; a partial copy is
+/// cleaned up on failure.
+pub fn move_file(from: &Path, to: &Path, replace: bool) -> io::Result<()> {
+    let res = if replace {
+        fs::rename(from, to)
+    } else {
+        rename_no_replace(from, to)
+    };
+    match res {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            copy_then_remove(from, to, replace)
+        }
+        res => res,
+    }
+}
+
+/// Renames a file unless the destination exists, with `renameat2` on Linux
+#[cfg(target_os = "linux")]
+fn rename_no_replace(from: &Path, to: &Path) -> io::Result<()> {
+    use rustix::fs::{renameat_with, RenameFlags, CWD};
+    use rustix::io::Errno;
+    match renameat_with(CWD, from, CWD, to, RenameFlags::NOREPLACE) {
+        // not supported by the kernel or filesystem
+        Err(Errno::INVAL | Errno::NOSYS) => check_then_rename(from, to),
+        res => res.map_err(io::Error::from),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn rename_no_replace(from: &Path, to: &Path) -> io::Result<()> {
+    check_then_rename(from, to)
+}
+
+/// Renames a file unless the destination exists, which isn't atomic
+fn check_then_rename(from: &Path, to: &Path) -> io::Result<()> {
+    if to.symlink_metadata().is_ok() {
+        return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+    }
+    fs::rename(from, to)
+}
+
+/// Copies a file or a directory then removes the original, keeping modified and accessed times
+fn copy_then_remove(from: &Path, to: &Path, replace: bool) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.is_dir() {
+        return copy_dir_then_remove(from, to);
+    }
+    // a broken symlink has no content to copy, the link itself is recreated
+    if metadata.is_symlink() && !from.exists() {
+        if replace && to.symlink_metadata().is_ok() {
+            fs::remove_file(to)?;
+        }
+        copy_symlink(from, to)?;
+        return fs::remove_file(from);
+    }
+    copy_with_times(from, to, replace)?;
+    fs::remove_file(from)
+}
+
+/// Copies a directory and its contents then removes the original. The directory is never
+/// copied over an existing one, and a partial copy is cleaned up on failure.
+fn copy_dir_then_remove(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir(to)?;
+    if let Err(err) = copy_dir_contents(from, to) {
+        let _ = fs::remove_dir_all(to);
+        return Err(err);
+    }
+    fs::remove_dir_all(from)
+}
+
+/// Copies the contents of a directory into an empty one, recreating symlinks rather than
+/// following them, then gives it the permissions and times of the original
+fn copy_dir_contents(from: &Path, to: &Path) -> io::Result<()> {
+    for dir_entry in fs::read_dir(from)? {
+        let dir_entry = dir_entry?;
+        let file_type = dir_entry.file_type()?;
+        let source = dir_entry.path();
+        let target = to.join(dir_entry.file_name());
+        if file_type.is_dir() {
+            fs::create_dir(&target)?;
+            copy_dir_contents(&source, &target)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(&source, &target)?;
+        } else {
+            copy_with_times(&source, &target, false)?;
+        }
+    }
+    let metadata = fs::metadata(from)?;
+    fs::set_permissions(to, metadata.permissions())?;
+    filetime::set_file_times(
+        to,
+        FileTime::from_last_access_time(&metadata),
+        FileTime::from_last_modification_time(&metadata),
+    )
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, _to: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("can't copy symlink {}", from.to_string_lossy()),
+    ))
+}
+
+/// Copies a file keeping its modified and accessed times, removing a partial copy on failure
+fn copy_with_times(from: &Path, to: &Path, replace: bool) -> io::Result<()> {
+    let metadata = fs::metadata(from)?;
+    let expected_len = metadata.len();
+    let copied = match copy_file(from, to, replace) {
+        Ok(copied) => copied,
+        // the file at the destination isn't ours to remove
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => return Err(err),
+        Err(err) => {
+            let _ = fs::remove_file(to);
+            return Err(err);
+        }
+    };
+    if copied != expected_len || fs::metadata(to)?.len() != expected_len {
+        let _ = fs::remove_file(to);
+        return Err(io::Error::other(format!(
+            "copy is incomplete ({copied} out of {expected_len} bytes)"
+        )));
+    }
+    if let Err(err) = filetime::set_file_times(
+        to,
+        FileTime::from_last_access_time(&metadata),
+        FileTime::from_last_modification_time(&metadata),
+    ) {
+        let _ = fs::remove_file(to);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Copies a file like [`fs::copy`], creating the destination only if it doesn't exist unless
+/// `replace` is set
+fn copy_file(from: &Path, to: &Path, replace: bool) -> io::Result<u64> {
+    if replace {
+        return fs::copy(from, to);
+    }
+    let mut source = fs::File::open(from)?;
+    let mut destination = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(to)?;
+    let copied = io::copy(&mut source, &mut destination)?;
+    destination.set_permissions(source.metadata()?.permissions())?;
+    Ok(copied)
+}
+
+/// Removes the directories under `root` that the `moved` files left empty,
+/// returning how many were (or would be, on dry runs) removed.
+///
+/// Only ancestors of moved files are considered, and `root` itself is kept.
+/// A directory that can't be read is never considered empty.
+pub fn remove_emptied_dirs(root: &Path, moved: &[&Path], dry_run: bool) -> usize {
+    let mut gone = moved
+        .iter()
+        .map(|path| path.to_path_buf())
+        .collect::<HashSet<_>>();
+    let mut dirs = moved
+        .iter()
+        .flat_map(|path| path.ancestors().skip(1))
+        .filter(|dir| dir.starts_with(root) && *dir != root)
+        .collect::<Vec<_>>();
+    // deepest first so that parents see their emptied children as gone
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    dirs.dedup();
+
+    let mut removed = 0;
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        let mut is_empty = true;
+        for entry in entries {
+            match entry {
+                Ok(entry) if gone.contains(&entry.path()) => {}
+                _ => {
+                    is_empty = false;
+                    break;
+                }
             }
         }
+        if is_empty && (dry_run || fs::remove_dir(dir).is_ok()) {
+            gone.insert(dir.to_path_buf());
+            removed += 1;
+        }
+    }
+    removed
+}
 
-        AutoMoveResult::Ok {
-            rule: self,
-            entries: result_entries,
+/// Checks if two files have the same contents.
+///
+/// Sizes are compared first so that files are only read when they could be equal.
+pub fn is_identical(a: &Path, b: &Path) -> io::Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    Ok(hash_file(a)? == hash_file(b)?)
+}
+
+fn hash_file(path: &Path) -> io::Result<u64> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = XxHash3_64::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            return Ok(hasher.finish());
         }
+        hasher.write(&buf[..read]);
+    }
+}
+
+/// Default `rename-template`, appending ` (1)`, ` (2)`, etc. before the extension
+pub const DEFAULT_RENAME_TEMPLATE: &str = "{stem} ({counter}){ext}";
+
+/// Placeholders that can be used in `rename-template`
+const RENAME_PLACEHOLDERS: [&str; 4] = ["{stem}", "{ext}", "{counter}", "{hash8}"];
+
+/// Errors if a `rename-template` has an unknown placeholder or could only give the same name
+pub fn check_rename_template(template: &str) -> anyhow::Result<()> {
+    let mut rest = template.to_string();
+    for placeholder in RENAME_PLACEHOLDERS {
+        rest = rest.replace(placeholder, "");
+    }
+    if let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .map_or(rest.len(), |end| start + end + 1);
+        bail!(
+            "Unknown placeholder {} in rename-template '{template}'",
+            &rest[start..end]
+        );
+    }
+    if !template.contains("{counter}") && !template.contains("{hash8}") {
+        bail!("rename-template '{template}' needs a {{counter}} or {{hash8}} placeholder");
+    }
+    Ok(())
+}
+
+/// Returns the first 8 hexadecimal digits of the hash of a file's contents, as used by the
+/// `{hash8}` placeholder of `rename-template`
+pub fn short_hash(path: &Path) -> io::Result<String> {
+    Ok(format!("{:016x}", hash_file(path)?)[..8].to_string())
+}
+
+/// Finds a free variant of `path` from a `rename-template`, counting up from 1 for
+/// `{counter}`, with `hash8` the [`short_hash`] of the file moved there.
+///
+/// Returns `None` if the template has no `{counter}` and the only name it gives is taken.
+pub fn conflict_free_path<F, E>(
+    path: &Path,
+    template: &str,
+    hash8: &str,
+    mut is_taken: F,
+) -> Result<Option<PathBuf>, E>
+where
+    F: FnMut(&Path) -> Result<bool, E>,
+{
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let filename = template
+        .replace("{stem}", &stem)
+        .replace("{ext}", &ext)
+        .replace("{hash8}", hash8);
+    let mut counter = 1;
+    loop {
+        let candidate = path.with_file_name(filename.replace("{counter}", &counter.to_string()));
+        if !is_taken(&candidate)? {
+            return Ok(Some(candidate));
+        }
+        if !filename.contains("{counter}") {
+            return Ok(None);
+        }
+        counter += 1;
     }
 }
 
 fn command_output_to_filename(mut out: &[u8]) -> OsString {
-    while out.first().map(u8::is_ascii_whitespace) == Some(true) {
+    while out.first().is_some_and(u8::is_ascii_whitespace) {
         out = &out[1..];
     }
-    while out.last().map(u8::is_ascii_whitespace) == Some(true) {
+    while out.last().is_some_and(u8::is_ascii_whitespace) {
         out = &out[..out.len() - 1];
     }
     #[cfg(unix)]
@@ -244,38 +1244,210 @@ fn command_output_to_filename(mut out: &[u8]) -> OsString {
     OsString::from(String::from_utf8_lossy(out).to_string())
 }
 
-/// Sets up a [`AutoMove`] from config
-pub fn from_config(
+impl AutoMove {
+    /// Sets up an [`AutoMove`] from config, `parent` limiting runs to the rules under it
+    pub fn from_config(
+        config: &Config,
+        config_dir: &Path,
+        parent: Option<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        check_rename_template(&config.automove.rename_template)?;
+        let mut rules = Vec::new();
+        for config_rule in &config.automove.rules {
+            if !config_rule.enabled {
+                continue;
+            }
+            let rule = rule_from_config(config, config_dir, config_rule)?;
+            for rule in expand_parent_glob(rule)? {
+                check_nested_destination(&rule)?;
+                rules.push(rule);
+            }
+        }
+
+        rules.sort_by_cached_key(AutoMoveRule::display_name);
+        Ok(Self { parent, rules })
+    }
+}
+
+/// Ensures a to-script exists and can be executed
+pub fn check_script(path: &Path) -> anyhow::Result<()> {
+    let metadata = match path.metadata() {
+        Ok(metadata) => metadata,
+        Err(err) => bail!("to-script {} can't be found: {err}", path.to_string_lossy()),
+    };
+    if !metadata.is_file() {
+        bail!("to-script {} is not a file", path.to_string_lossy());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            bail!("to-script {} is not executable", path.to_string_lossy());
+        }
+    }
+    Ok(())
+}
+
+/// Errors if a recursive rule moves files to a subdirectory of its `parent`,
+/// where they could be matched and moved again.
+///
+/// Non-recursive rules are only warned about, with the `auto-move` command.
+pub fn check_nested_destination(rule: &AutoMoveRule) -> anyhow::Result<()> {
+    if rule.recursive && rule.has_nested_destination() {
+        bail!(
+            "Auto-move rule '{}' is recursive and moves files inside its parent directory",
+            rule.display_name()
+        );
+    }
+    Ok(())
+}
+
+/// Sets up an [`AutoMoveRule`] from its config, without expanding a glob `parent`
+pub fn rule_from_config(
     config: &Config,
     config_dir: &Path,
-    parent: Option<PathBuf>,
-) -> anyhow::Result<AutoMove> {
-    let mut rules = Vec::new();
-    for config_rule in &config.automove.rules {
-        let match_rules = rules::compile_config_rules(&config_rule.match_rules)?;
-        rules.push(AutoMoveRule {
-            custom_name: config_rule.name.clone(),
-            directory: PathBuf::from(shellexpand::env(&config_rule.parent)?.as_ref()),
-            to: PathBuf::from(shellexpand::env(&config_rule.to)?.as_ref()),
-            to_script: if let Some(path) = &config_rule.to_script {
-                let expanded_path = shellexpand::env(path)?;
-                let expanded_path = Path::new(expanded_path.as_ref());
-                if expanded_path.is_absolute() {
-                    Some(expanded_path.to_path_buf())
-                } else {
-                    let mut path = config_dir.to_path_buf();
-                    path.push(expanded_path);
-                    Some(path)
-                }
+    config_rule: &config::AutoMoveRule,
+) -> anyhow::Result<AutoMoveRule> {
+    if config_rule.to_script_batch && config_rule.to_script.is_none() {
+        bail!(
+            "Auto-move rule '{}' has to-script-batch set without a to-script",
+            config_rule.name.as_ref().unwrap_or(&config_rule.parent)
+        );
+    }
+    if config_rule.group_by_extension && config_rule.to_script.is_some() {
+        bail!(
+            "Auto-move rule '{}' can't have both group-by-extension and to-script",
+            config_rule.name.as_ref().unwrap_or(&config_rule.parent)
+        );
+    }
+    let to = match &config.automove.default_to {
+        Some(default_to) if config_rule.to.is_empty() && config_rule.route.is_empty() => default_to,
+        _ => &config_rule.to,
+    };
+    if to.is_empty() && config_rule.route.is_empty() {
+        bail!(
+            "Auto-move rule '{}' needs either a to or a route, as there is no automove.default-to",
+            config_rule.name.as_ref().unwrap_or(&config_rule.parent)
+        );
+    }
+    if config_rule.to_relative {
+        check_relative_destinations(config_rule, to)?;
+    }
+    if config_rule.move_directories && config_rule.recursive {
+        bail!(
+            "Auto-move rule '{}' can't have both move-directories and recursive",
+            config_rule.name.as_ref().unwrap_or(&config_rule.parent)
+        );
+    }
+    let match_rules =
+        rules::compile_config_rules(&config_rule.match_rules, config_rule.case_insensitive)?;
+    let mut route = Vec::new();
+    for (ext, to) in &config_rule.route {
+        let ext_rule = rules::compile_config_rules(
+            &vec![config::MatchRule::Extension { ext: ext.clone() }],
+            config_rule.case_insensitive,
+        )?;
+        route.push((ext, ext_rule, PathBuf::from(shellexpand::env(to)?.as_ref())));
+    }
+    // try longer extensions first so that `tar.gz` takes precedence over `gz`
+    route.sort_by_key(|(ext, _, _)| Reverse(ext.len()));
+    let recursive_ignore_rules = rules::compile_config_rules(
+        &config_rule.recursive_ignore_children,
+        config_rule.case_insensitive,
+    )?;
+    Ok(AutoMoveRule {
+        custom_name: config_rule.name.clone(),
+        directory: PathBuf::from(shellexpand::env(&config_rule.parent)?.as_ref()),
+        from_glob: false,
+        to: PathBuf::from(shellexpand::env(to)?.as_ref()),
+        route: route
+            .into_iter()
+            .map(|(_, ext_rule, to)| (ext_rule, to))
+            .collect(),
+        to_relative: config_rule.to_relative,
+        group_by_extension: config_rule
+            .group_by_extension
+            .then(|| config_rule.no_extension_dir.clone().into()),
+        to_script_batch: config_rule.to_script_batch,
+        to_script_skip_code: config_rule.to_script_skip_code,
+        script_timeout: match config.automove.script_timeout {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        },
+        to_script: if let Some(path) = &config_rule.to_script {
+            let expanded_path = shellexpand::env(path)?;
+            let expanded_path = Path::new(expanded_path.as_ref());
+            if expanded_path.is_absolute() {
+                Some(expanded_path.to_path_buf())
             } else {
-                None
-            },
-            match_rules,
-        });
+                let mut path = config_dir.to_path_buf();
+                path.push(expanded_path);
+                Some(path)
+            }
+        } else {
+            None
+        },
+        rename: match &config_rule.rename {
+            Some(_) if config_rule.to_script.is_some() => bail!(
+                "Auto-move rule '{}' can't have both rename and to-script",
+                config_rule.name.as_ref().unwrap_or(&config_rule.parent)
+            ),
+            Some(rename) => Some(Rename {
+                from: Regex::new(&rename.from)?,
+                to_name: rename.to_name.clone(),
+            }),
+            None => None,
+        },
+        match_rules,
+        recursive: config_rule.recursive,
+        recursive_ignore_rules,
+        move_directories: config_rule.move_directories,
+        overwrite_if: rules::compile_config_rules(&config.automove.overwrite_if, false)?,
+    })
+}
+
+/// Errors unless a `to-relative` rule is recursive and its destinations are relative paths
+fn check_relative_destinations(config_rule: &config::AutoMoveRule, to: &str) -> anyhow::Result<()> {
+    let name = config_rule.name.as_ref().unwrap_or(&config_rule.parent);
+    if !config_rule.recursive {
+        bail!("Auto-move rule '{name}' has to-relative set without recursive");
     }
+    let destinations = std::iter::once(to).chain(config_rule.route.values().map(String::as_str));
+    for to in destinations {
+        if Path::new(shellexpand::env(to)?.as_ref()).is_absolute() {
+            bail!(
+                "Auto-move rule '{name}' has to-relative set, but its destination {to} is absolute"
+            );
+        }
+    }
+    Ok(())
+}
 
-    rules.sort_by_cached_key(AutoMoveRule::display_name);
-    Ok(AutoMove { parent, rules })
+/// Returns the name of the subdirectory grouping files with the same extension as `filename`
+fn extension_dir(filename: &OsStr, no_extension_dir: &OsStr) -> OsString {
+    match Path::new(filename).extension() {
+        Some(ext) => ext.to_string_lossy().to_lowercase().into(),
+        None => no_extension_dir.to_os_string(),
+    }
+}
+
+#[test]
+fn test_extension_dir() {
+    let no_ext = OsStr::new("no-extension");
+    assert_eq!("jpg", extension_dir(OsStr::new("IMG_0001.JPG"), no_ext));
+    assert_eq!("gz", extension_dir(OsStr::new("backup.tar.gz"), no_ext));
+    assert_eq!("no-extension", extension_dir(OsStr::new("README"), no_ext));
+    assert_eq!("no-extension", extension_dir(OsStr::new(".bashrc"), no_ext));
+}
+
+#[test]
+fn test_parallel_map() {
+    let items = (0..100).collect::<Vec<u64>>();
+    assert_eq!(
+        items.iter().map(|i| i * 2).collect::<Vec<_>>(),
+        parallel_map(&items, |i| i * 2)
+    );
+    assert!(parallel_map(&Vec::<u64>::new(), |i| i * 2).is_empty());
 }
 
 #[test]
@@ -283,3 +1455,481 @@ fn test_output_to_filename() {
     assert_eq!("hello", command_output_to_filename(b"  hello \n"));
     assert_eq!("hé", command_output_to_filename(b"h\xC3\xA9"));
 }
+
+#[test]
+fn test_conflict_free_path() {
+    let taken = [PathBuf::from("/a/b.txt"), PathBuf::from("/a/b (1).txt")];
+    let is_taken = |path: &Path| Ok::<_, ()>(taken.iter().any(|taken| taken == path));
+    let free = conflict_free_path(Path::new("/a/b.txt"), DEFAULT_RENAME_TEMPLATE, "", is_taken);
+    assert_eq!(Ok(Some(PathBuf::from("/a/b (2).txt"))), free);
+    let free = conflict_free_path(Path::new("/a/.hidden"), DEFAULT_RENAME_TEMPLATE, "", |_| {
+        Ok::<_, ()>(false)
+    });
+    assert_eq!(Ok(Some(PathBuf::from("/a/.hidden (1)"))), free);
+
+    let free = conflict_free_path(Path::new("/a/b.txt"), "{stem}-{counter}{ext}", "", is_taken);
+    assert_eq!(Ok(Some(PathBuf::from("/a/b-1.txt"))), free);
+    let template = "{stem}-{hash8}{ext}";
+    let free = conflict_free_path(Path::new("/a/b.txt"), template, "0123abcd", is_taken);
+    assert_eq!(Ok(Some(PathBuf::from("/a/b-0123abcd.txt"))), free);
+    let free = conflict_free_path(Path::new("/a/b.txt"), "{stem} (1){ext}", "", is_taken);
+    assert_eq!(Ok(None), free);
+
+    assert!(check_rename_template("{stem}_{counter}{ext}").is_ok());
+    assert!(check_rename_template("{stem}{ext}").is_err());
+    assert!(check_rename_template("{stem}-{count}{ext}").is_err());
+}
+
+#[test]
+fn test_rename() {
+    let rename = Rename {
+        from: Regex::new(r"^IMG_(\d+)\.jpg$").unwrap(),
+        to_name: "photo_$1.jpg".to_string(),
+    };
+    assert_eq!("photo_0042.jpg", rename.apply("IMG_0042.jpg"));
+    assert_eq!("notes.txt", rename.apply("notes.txt"));
+}
+
+#[test]
+fn test_copy_preserves_times() {
+    let dir = test_fixture("copy-times", &["old.txt"]);
+    let from = dir.join("old.txt");
+    let to = dir.join("new.txt");
+    fs::write(&from, "contents").unwrap();
+    let old_time = FileTime::from_unix_time(1_000_000_000, 0);
+    filetime::set_file_times(&from, old_time, old_time).unwrap();
+
+    copy_then_remove(&from, &to, false).unwrap();
+    let metadata = fs::metadata(&to).unwrap();
+    assert!(!from.exists());
+    assert_eq!(old_time, FileTime::from_last_modification_time(&metadata));
+    assert_eq!(old_time, FileTime::from_last_access_time(&metadata));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_move_file_no_replace() {
+    let dir = std::env::temp_dir().join(format!("shinydir-no-replace-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let from = dir.join("from.txt");
+    let to = dir.join("to.txt");
+    fs::write(&from, "new").unwrap();
+    fs::write(&to, "existing").unwrap();
+
+    let err = move_file(&from, &to, false).unwrap_err();
+    assert_eq!(io::ErrorKind::AlreadyExists, err.kind());
+    let err = copy_then_remove(&from, &to, false).unwrap_err();
+    assert_eq!(io::ErrorKind::AlreadyExists, err.kind());
+    assert_eq!("new", fs::read_to_string(&from).unwrap());
+    assert_eq!("existing", fs::read_to_string(&to).unwrap());
+
+    move_file(&from, &to, true).unwrap();
+    assert!(!from.exists());
+    assert_eq!("new", fs::read_to_string(&to).unwrap());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_recheck() {
+    let files = ["modified.log", "removed.log", "replaced.log", "same.log"];
+    let root = test_fixture("recheck", &files);
+    let automove = test_automove(
+        &root,
+        "[[automove.rules]]\nparent = '{root}'\nto = '{root}/archive'\nmatch = [{ ext = \"log\" }]\n",
+    )
+    .unwrap();
+    let entries = test_entries(&automove);
+
+    fs::write(root.join("modified.log"), "contents").unwrap();
+    fs::remove_file(root.join("replaced.log")).unwrap();
+    fs::create_dir(root.join("replaced.log")).unwrap();
+    fs::remove_file(root.join("removed.log")).unwrap();
+    assert_eq!(
+        vec![false, false, false, true],
+        automove.rules[0].recheck(&entries.iter().collect::<Vec<_>>())
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_move_directories() {
+    let root = test_fixture("move-dirs", &["project/src/main.rs", "project.txt"]);
+    fs::write(root.join("project/src/main.rs"), "fn main() {}").unwrap();
+    let rule = "[[automove.rules]]\nparent = '{root}'\nto = '{root}/archive'\n\
+        match = [{ glob = \"project*\" }]\n";
+    let automove = test_automove(&root, rule).unwrap();
+    assert_eq!(
+        vec!["project.txt -> archive/project.txt"],
+        test_moves(&automove, &root)
+    );
+    let automove = test_automove(&root, &format!("{rule}move-directories = true\n")).unwrap();
+    assert_eq!(
+        vec![
+            "project -> archive/project",
+            "project.txt -> archive/project.txt"
+        ],
+        test_moves(&automove, &root)
+    );
+
+    copy_dir_then_remove(&root.join("project"), &root.join("copy")).unwrap();
+    assert!(!root.join("project").exists());
+    assert_eq!(
+        "fn main() {}",
+        fs::read_to_string(root.join("copy/src/main.rs")).unwrap()
+    );
+    fs::create_dir(root.join("project")).unwrap();
+    let err = copy_dir_then_remove(&root.join("copy"), &root.join("project")).unwrap_err();
+    assert_eq!(io::ErrorKind::AlreadyExists, err.kind());
+    assert!(root.join("copy/src/main.rs").exists());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_broken_symlinks() {
+    let root = test_fixture("broken-links", &["a.txt"]);
+    std::os::unix::fs::symlink("a.txt", root.join("good")).unwrap();
+    std::os::unix::fs::symlink("missing.txt", root.join("broken")).unwrap();
+    let automove = test_automove(
+        &root,
+        "[[automove.rules]]\nparent = '{root}'\nto = '{root}/dead'\n\
+        match = [{ type = \"broken-symlink\" }]\n",
+    )
+    .unwrap();
+    assert_eq!(vec!["broken -> dead/broken"], test_moves(&automove, &root));
+
+    fs::create_dir(root.join("dead")).unwrap();
+    copy_then_remove(&root.join("broken"), &root.join("dead/broken"), false).unwrap();
+    assert!(root.join("broken").symlink_metadata().is_err());
+    assert_eq!(
+        Path::new("missing.txt"),
+        fs::read_link(root.join("dead/broken")).unwrap()
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_entry_error() {
+    let err = EntryError::wrap(
+        format_err!("Moving to /b/a.txt would overwrite a file"),
+        PathBuf::from("/a/a.txt"),
+        Some(PathBuf::from("/b/a.txt")),
+    );
+    assert_eq!("Moving to /b/a.txt would overwrite a file", err.to_string());
+    let paths = err.downcast_ref::<EntryError>().unwrap();
+    assert_eq!(Path::new("/a/a.txt"), paths.file);
+    assert_eq!(Some(Path::new("/b/a.txt")), paths.move_to.as_deref());
+}
+
+#[test]
+fn test_default_to() {
+    let automove = test_automove(
+        Path::new("/"),
+        "default-to = \"/archive\"\n\
+        [[automove.rules]]\nparent = \"/a\"\nmatch = []\n\
+        [[automove.rules]]\nparent = \"/b\"\nto = \"/b-files\"\nmatch = []\n\
+        [[automove.rules]]\nparent = \"/c\"\nroute = { txt = \"/c-text\" }\nmatch = []\n",
+    )
+    .unwrap();
+    let destinations = automove
+        .rules
+        .iter()
+        .map(|rule| rule.to.as_path())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![Path::new("/archive"), Path::new("/b-files"), Path::new("")],
+        destinations
+    );
+
+    let rule = "[[automove.rules]]\nparent = \"/a\"\nmatch = []\n";
+    assert!(test_automove(Path::new("/"), rule).is_err());
+}
+
+/// Sets up auto-move from the contents of the `[automove]` table, where `{root}` stands for
+/// the fixture directory (paths are best put in single-quoted strings)
+#[cfg(test)]
+fn test_automove(root: &Path, automove_config: &str) -> anyhow::Result<AutoMove> {
+    let automove_config = automove_config.replace("{root}", &root.to_string_lossy());
+    let config: Config =
+        toml::from_str(&format!("[settings]\n[dir]\n[automove]\n{automove_config}")).unwrap();
+    AutoMove::from_config(&config, root, None)
+}
+
+/// Runs the first rule, returning its entries sorted by file
+#[cfg(test)]
+fn test_entries(automove: &AutoMove) -> Vec<AutoMoveResultEntry> {
+    let AutoMoveResult::Ok { entries, .. } = automove.rules[0].run() else {
+        panic!("fixture directory could not be read");
+    };
+    let mut entries = entries.into_iter().map(Result::unwrap).collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.file.cmp(&b.file));
+    entries
+}
+
+/// Runs the first rule, returning its moves as `file -> move_to` relative to `root`
+#[cfg(test)]
+fn test_moves(automove: &AutoMove, root: &Path) -> Vec<String> {
+    test_entries(automove)
+        .iter()
+        .map(|entry| {
+            format!(
+                "{} -> {}",
+                entry.file.strip_prefix(root).unwrap().to_string_lossy(),
+                entry.move_to.strip_prefix(root).unwrap().to_string_lossy()
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_to_template() {
+    use chrono::TimeZone;
+
+    let root = test_fixture("to-template", &["src/photo.jpg", "src/notes"]);
+    let modified = Local.with_ymd_and_hms(2021, 3, 4, 12, 0, 0).unwrap();
+    let modified = FileTime::from_unix_time(modified.timestamp(), 0);
+    for file in ["src/photo.jpg", "src/notes"] {
+        filetime::set_file_mtime(root.join(file), modified).unwrap();
+    }
+    let automove = test_automove(
+        &root,
+        "[[automove.rules]]\nparent = '{root}/src'\nto = '{root}/dst/{year}/{month}-{day}/{ext}'\n\
+        match = [{ glob = \"*\" }]\n",
+    )
+    .unwrap();
+    assert_eq!(
+        vec![
+            "src/notes -> dst/2021/03-04/notes",
+            "src/photo.jpg -> dst/2021/03-04/jpg/photo.jpg",
+        ],
+        test_moves(&automove, &root)
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_parent_glob() {
+    let files = [
+        "projects/a/downloads/x.txt",
+        "projects/b/downloads/y.txt",
+        "projects/c/other.txt",
+    ];
+    let root = test_fixture("parent-glob", &files);
+    let automove = test_automove(
+        &root,
+        "[[automove.rules]]\nname = 'downloads'\nparent = '{root}/projects/*/downloads'\n\
+        to = '{root}/dst'\nmatch = [{ ext = \"txt\" }]\n\
+        [[automove.rules]]\nparent = '{root}/missing/*'\nto = '{root}/dst'\nmatch = []\n",
+    )
+    .unwrap();
+    let names = automove
+        .rules
+        .iter()
+        .map(AutoMoveRule::display_name)
+        .collect::<Vec<_>>();
+    let root_str = root.to_string_lossy();
+    assert_eq!(
+        vec![
+            format!("{root_str}/missing/*"),
+            format!("downloads ({root_str}/projects/a/downloads)"),
+            format!("downloads ({root_str}/projects/b/downloads)"),
+        ],
+        names
+    );
+    // a glob that matches nothing is reported as a missing directory
+    assert!(matches!(
+        automove.rules[0].run(),
+        AutoMoveResult::DirDoesNotExist { .. }
+    ));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_recursive() {
+    let files = [
+        "src/a.txt",
+        "src/sub/b.txt",
+        "src/sub/deep/c.txt",
+        "src/sub/d.png",
+        "src/skip/e.txt",
+    ];
+    let root = test_fixture("recursive", &files);
+    let automove = test_automove(
+        &root,
+        "[[automove.rules]]\nparent = '{root}/src'\nto = '{root}/dst'\n\
+        match = [{ ext = \"txt\" }]\nrecursive = true\nrecursive-ignore = [{ name = \"skip\" }]\n",
+    )
+    .unwrap();
+    assert_eq!(
+        vec![
+            "src/a.txt -> dst/a.txt",
+            "src/sub/b.txt -> dst/sub/b.txt",
+            "src/sub/deep/c.txt -> dst/sub/deep/c.txt",
+        ],
+        test_moves(&automove, &root)
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_recursive_unreadable() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = test_fixture("recursive-unreadable", &["src/a.txt", "src/locked/b.txt"]);
+    let locked = root.join("src/locked");
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+    if fs::read_dir(&locked).is_ok() {
+        // permissions are not enforced, e.g. when running as root
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::remove_dir_all(root).unwrap();
+        return;
+    }
+    let automove = test_automove(
+        &root,
+        "[[automove.rules]]\nparent = '{root}/src'\nto = '{root}/dst'\n\
+        match = [{ ext = \"txt\" }]\nrecursive = true\n",
+    )
+    .unwrap();
+    let AutoMoveResult::Ok {
+        entries, errors, ..
+    } = automove.rules[0].run()
+    else {
+        panic!("directory should exist");
+    };
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(1, entries.len());
+    assert_eq!(
+        vec![locked],
+        errors.into_iter().map(|(path, _)| path).collect::<Vec<_>>()
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_remove_emptied_dirs() {
+    let files = [
+        "a/b/moved.txt",
+        "a/c/moved.txt",
+        "a/c/kept.txt",
+        "untouched/x",
+    ];
+    let root = test_fixture("emptied", &files);
+    fs::create_dir_all(root.join("already-empty")).unwrap();
+    let moved = [root.join("a/b/moved.txt"), root.join("a/c/moved.txt")];
+    let moved = moved.iter().map(PathBuf::as_path).collect::<Vec<_>>();
+
+    assert_eq!(1, remove_emptied_dirs(&root, &moved, true));
+    assert!(root.join("a/b").exists());
+
+    for path in &moved {
+        fs::remove_file(path).unwrap();
+    }
+    assert_eq!(1, remove_emptied_dirs(&root, &moved, false));
+    assert!(!root.join("a/b").exists());
+    assert!(root.join("a/c").exists());
+    assert!(root.join("already-empty").exists());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_is_identical() {
+    let dir = test_fixture("identical", &["a", "b", "c", "d"]);
+    fs::write(dir.join("a"), "same contents").unwrap();
+    fs::write(dir.join("b"), "same contents").unwrap();
+    fs::write(dir.join("c"), "same lengths!").unwrap();
+    fs::write(dir.join("d"), "other").unwrap();
+    assert!(is_identical(&dir.join("a"), &dir.join("b")).unwrap());
+    assert!(!is_identical(&dir.join("a"), &dir.join("c")).unwrap());
+    assert!(!is_identical(&dir.join("a"), &dir.join("d")).unwrap());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_output_with_timeout() {
+    let output = output_with_timeout(
+        &mut Command::new("cat"),
+        Some(b"hello\n".to_vec()),
+        Some(Duration::from_secs(5)),
+    )
+    .unwrap();
+    assert_eq!(b"hello\n", output.stdout.as_slice());
+    let err = output_with_timeout(
+        Command::new("sleep").arg("5"),
+        None,
+        Some(Duration::from_millis(50)),
+    )
+    .unwrap_err();
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
+}
+
+#[test]
+fn test_may_overwrite() {
+    let root = test_fixture("overwrite", &["dst/cache.tmp", "dst/notes.txt"]);
+    let automove = test_automove(
+        &root,
+        "overwrite-if = [{ ext = \"tmp\" }]\n\
+        [[automove.rules]]\nparent = '{root}/src'\nto = '{root}/dst'\nmatch = []\n",
+    )
+    .unwrap();
+    let rule = &automove.rules[0];
+    assert!(rule.may_overwrite(&root.join("dst/cache.tmp")).unwrap());
+    assert!(!rule.may_overwrite(&root.join("dst/notes.txt")).unwrap());
+    assert!(!rule.may_overwrite(&root.join("dst/missing.tmp")).unwrap());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_to_relative() {
+    let root = test_fixture("to-relative", &["a.log", "p1/b.log", "p1/archive/c.log"]);
+    let automove = test_automove(
+        &root,
+        "[[automove.rules]]\nparent = '{root}'\nto = \"archive\"\nto-relative = true\n\
+        match = [{ ext = \"log\" }]\nrecursive = true\n",
+    )
+    .unwrap();
+    assert_eq!(
+        vec!["a.log -> archive/a.log", "p1/b.log -> p1/archive/b.log"],
+        test_moves(&automove, &root)
+    );
+
+    let rule = "[[automove.rules]]\nparent = '{root}'\nto = '{root}/archive'\n\
+        to-relative = true\nmatch = []\nrecursive = true\n";
+    assert!(test_automove(&root, rule).is_err());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_to_script_skip() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let files = [
+        "src/move.txt",
+        "src/token.txt",
+        "src/empty.txt",
+        "src/code.txt",
+    ];
+    let root = test_fixture("script-skip", &files);
+    let script = root.join("to-script.sh");
+    fs::write(
+        &script,
+        "#!/bin/sh\ncase \"$1\" in\n*token*) echo SKIP ;;\n*empty*) ;;\n*code*) exit 3 ;;\n\
+        *) echo moved.txt ;;\nesac\n",
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    let automove = test_automove(
+        &root,
+        "[[automove.rules]]\nparent = '{root}/src'\nto = '{root}/dst'\nmatch = [{ ext = \"txt\" }]\n\
+        to-script = '{root}/to-script.sh'\nto-script-skip-code = 3\n",
+    )
+    .unwrap();
+    assert_eq!(
+        vec!["src/move.txt -> dst/moved.txt"],
+        test_moves(&automove, &root)
+    );
+    fs::remove_dir_all(root).unwrap();
+}
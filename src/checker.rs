@@ -1,9 +1,19 @@
-use crate::config::Config;
+use crate::config::{Config, DirectoryConfig, MatchRule, Settings};
 use crate::rules::{self, FileMatchRule, FileType};
-use anyhow::bail;
-use colored::Colorize;
+use crate::scan_cache::{self, ScanCache};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::Infallible;
 use std::fs::{self, DirEntry, Metadata};
+use std::io;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Name of the files listing entries to leave out of checks, with the `.gitignore` syntax
+const IGNORE_FILENAME: &str = ".shinydirignore";
 
 #[derive(Debug, Clone)]
 pub struct Checker {
@@ -11,37 +21,99 @@ pub struct Checker {
     pub directories: Vec<DirectoryChecker>,
 }
 
-impl Checker {
-    pub fn check_empty(&self, config: &Config) -> anyhow::Result<()> {
-        if self.directories.is_empty() {
-            if config.settings.color {
-                bail!(
-                    "{} No directories were configured to be checked.",
-                    "(!)".bold()
-                );
-            }
-            bail!("(!) No directories were configured to be checked.");
-        }
-        Ok(())
-    }
-}
-
 /// Checker configuration for a directory
 #[derive(Debug, Clone)]
-#[allow(clippy::module_name_repetitions)]
+#[allow(clippy::module_name_repetitions, clippy::struct_excessive_bools)]
 pub struct DirectoryChecker {
+    /// Name of the directory in the config
+    pub name: String,
     /// Path of the directory
     pub path: PathBuf,
     /// Valid file rules. Any file that doesn't match will be reported as misplaced
     pub rules: FileMatchRule,
+    /// Files that are always reported as misplaced, even if they match `rules`
+    pub disallowed_rules: FileMatchRule,
+    /// Descriptions of the configured allowed rules, to explain why files are misplaced
+    pub labels: RuleLabels,
     /// Whether to check rules recursively
     pub recursive: bool,
+    /// Maximum depth to descend to when `recursive` is `true`, 0 being the directory itself
+    pub max_depth: Option<usize>,
     /// Children directories to ignore when `recursive` is `true`
     pub recursive_ignore_rules: FileMatchRule,
+    /// Whether to descend into symlinked directories when `recursive` is `true`
+    pub recursive_follow_symlinks: bool,
+    /// Whether not to descend into directories whose name starts with `.` when `recursive` is `true`
+    pub skip_hidden_dirs: bool,
+    /// Whether not to descend into directories on another filesystem when `recursive` is `true`
+    pub one_filesystem: bool,
+    /// Whether to leave out entries ignored by `.gitignore` files, and `.git` directories
+    pub respect_gitignore: bool,
+    /// Overrides the `color` setting for this directory's report
+    pub color: Option<bool>,
+    /// Overrides the `use-unicode` setting for this directory's report
+    pub unicode: Option<bool>,
+    /// Whether to reuse the results of previous runs for directories that haven't changed
+    pub cache: bool,
+    /// Whether symlinks are checked as the file they point to, rather than as symlinks
+    pub follow_symlinks: bool,
+    /// Entries never reported, from the `ignore-paths` setting
+    pub ignore_paths: Vec<glob::Pattern>,
+}
+
+/// Descriptions of the rules of a directory, `None` meaning anything is allowed
+#[derive(Debug, Clone, Default)]
+pub struct RuleLabels {
+    pub allowed_dirs: Option<Vec<String>>,
+    pub allowed_files: Option<Vec<String>>,
+    pub disallowed: Vec<String>,
+}
+
+/// Why a file is misplaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueReason {
+    /// It matches none of the allowed rules
+    NotAllowed,
+    /// It matches a disallowed rule
+    Disallowed,
+    /// It is a symlink whose target doesn't exist, and matches none of the allowed rules
+    BrokenSymlink,
+}
+
+/// Kind of a misplaced entry, which reports list it under
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    Directory,
+    /// Regular files, along with the special files that are neither directories nor symlinks
+    File,
+    /// Symlinks that are not followed
+    Symlink,
+    BrokenSymlink,
+}
+
+impl IssueKind {
+    /// Every kind, in the order reports list them
+    pub const ALL: [Self; 4] = [
+        Self::Directory,
+        Self::File,
+        Self::Symlink,
+        Self::BrokenSymlink,
+    ];
+
+    /// Heading of the entries of this kind in reports
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Directory => "Directories",
+            Self::File => "Files",
+            Self::Symlink => "Symlinks",
+            Self::BrokenSymlink => "Broken symlinks",
+        }
+    }
 }
 
 /// Result from attempting to check a directory
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 #[allow(clippy::module_name_repetitions)]
 pub enum CheckerResult {
     Ok(Report),
@@ -50,12 +122,14 @@ pub enum CheckerResult {
 }
 
 /// A report for a directory that was checked
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Report {
     /// Path of the checked file
     pub path: PathBuf,
     /// Reported issues for the directory itself and children
     pub issues: Vec<ReportIssue>,
+    /// Entries that couldn't be read, and were therefore not checked
+    pub errors: Vec<(PathBuf, io::Error)>,
 }
 
 /// A misplaced file
@@ -65,27 +139,71 @@ pub struct ReportIssue {
     path: PathBuf,
     /// Current metadata of the file
     metadata: Metadata,
+    /// Why the file is misplaced
+    reason: IssueReason,
 }
 
 impl Checker {
-    /// Executes directory rules to get a list of misplaced files
-    pub fn run(&self) -> Vec<CheckerResult> {
-        self.directories
-            .iter()
-            .filter(|directory| {
-                if let Some(parent) = &self.parent {
-                    directory.path.starts_with(parent)
-                } else {
-                    true
-                }
-            })
-            .map(DirectoryChecker::check)
-            .collect()
+    /// Executes directory rules to get a list of misplaced files, alongside the checked directory.
+    ///
+    /// `cache` is used for the directories that enable it.
+    pub fn run(&self, cache: Option<&mut ScanCache>) -> Vec<(&DirectoryChecker, CheckerResult)> {
+        let mut results = Vec::new();
+        let Ok(()) = self.run_with(cache, |directory, result, _| {
+            results.push((directory, result));
+            Ok::<_, Infallible>(())
+        });
+        results
+    }
+
+    /// Like [`Checker::run`], but hands each directory's result to `f` as soon as it's checked,
+    /// along with how long checking it took.
+    ///
+    /// Stops at the first error returned by `f`.
+    pub fn run_with<'a, E>(
+        &'a self,
+        mut cache: Option<&mut ScanCache>,
+        mut f: impl FnMut(&'a DirectoryChecker, CheckerResult, Duration) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let directories = self.directories.iter().filter(|directory| {
+            if let Some(parent) = &self.parent {
+                directory.path.starts_with(parent)
+            } else {
+                true
+            }
+        });
+        for directory in directories {
+            let cache = cache.as_deref_mut().filter(|_| directory.cache);
+            let started = Instant::now();
+            let result = directory.check(cache);
+            f(directory, result, started.elapsed())?;
+        }
+        Ok(())
     }
 }
 
+/// State shared by the directories checked in a single [`DirectoryChecker::check`]
+struct Walk<'a> {
+    /// Filesystem to stay on, if any
+    root_device: Option<u64>,
+    /// Canonical paths of directories already checked, so that symlink loops are only walked once
+    visited: HashSet<PathBuf>,
+    /// Ignore files of the directories above, the deepest last
+    ignores: Vec<Gitignore>,
+    cache: Option<&'a mut ScanCache>,
+}
+
 impl DirectoryChecker {
-    pub fn check(&self) -> CheckerResult {
+    /// Returns the settings to display this directory's report with
+    pub fn settings(&self, settings: &Settings) -> Settings {
+        Settings {
+            color: self.color.unwrap_or(settings.color),
+            unicode: self.unicode.unwrap_or(settings.unicode),
+            ..settings.clone()
+        }
+    }
+
+    pub fn check(&self, cache: Option<&mut ScanCache>) -> CheckerResult {
         match fs::metadata(&self.path) {
             Ok(md) if md.is_dir() => (),
             Ok(_) => {
@@ -98,57 +216,351 @@ impl DirectoryChecker {
                     path: self.path.clone(),
                 };
             }
+        }
+        let mut walk = Walk {
+            root_device: if self.one_filesystem {
+                device_id(&self.path)
+            } else {
+                None
+            },
+            visited: HashSet::new(),
+            ignores: Vec::new(),
+            cache,
         };
-        self.check_dir(&self.path)
+        if self.uses_file_state() {
+            debug!(
+                "{}: not cached, rules depend on the files themselves",
+                self.path.to_string_lossy()
+            );
+            walk.cache = None;
+        }
+        if let Some(cache) = walk.cache.as_deref_mut() {
+            cache.add_root(&self.path);
+        }
+        self.check_dir(&self.path, 0, scan_cache::fingerprint(self), &mut walk)
     }
 
-    fn check_dir(&self, path: &Path) -> CheckerResult {
+    /// Checks a directory's entries, descending into children when recursive.
+    ///
+    /// `stamp` identifies the config and ignore files the directory is checked with,
+    /// to tell if its cached scan can be reused.
+    fn check_dir(&self, path: &Path, depth: usize, stamp: u64, walk: &mut Walk) -> CheckerResult {
         let dir_entries = match fs::read_dir(path) {
-            Ok(entries) => entries,
-            Err(_) => {
-                return CheckerResult::NotADirectory {
-                    path: self.path.clone(),
-                };
+            Ok(dir_entries) => dir_entries,
+            Err(err) => {
+                debug!("{}: couldn't be read: {err}", path.to_string_lossy());
+                return CheckerResult::Ok(Report {
+                    path: path.to_path_buf(),
+                    issues: Vec::new(),
+                    errors: vec![(path.to_path_buf(), err)],
+                });
             }
         };
+        if let Ok(canonical_path) = fs::canonicalize(path) {
+            if !walk.visited.insert(canonical_path) {
+                return CheckerResult::Ok(Report {
+                    path: path.to_path_buf(),
+                    issues: Vec::new(),
+                    errors: Vec::new(),
+                });
+            }
+        }
 
+        let parent_ignores = walk.ignores.len();
+        let mut ignore_filenames = vec![IGNORE_FILENAME];
+        if self.respect_gitignore {
+            ignore_filenames.insert(0, ".gitignore");
+        }
+        for filename in &ignore_filenames {
+            walk.ignores.extend(load_ignore_file(path, filename));
+        }
+        let stamp = match walk.cache {
+            Some(_) => scan_cache::stamp(stamp, path, &ignore_filenames),
+            None => stamp,
+        };
+
+        let cached = walk
+            .cache
+            .as_deref_mut()
+            .and_then(|cache| cache.get(path, stamp))
+            .and_then(|cached| {
+                let issues = cached_issues(&cached.issues, self.follow_symlinks)?;
+                Some((issues, cached.subdirs.clone()))
+            });
+        let (mut report, subdirs) = if let Some((issues, subdirs)) = cached {
+            debug!("{}: using cached scan", path.to_string_lossy());
+            let report = Report {
+                path: path.to_path_buf(),
+                issues,
+                errors: Vec::new(),
+            };
+            (report, subdirs)
+        } else {
+            debug!("{}: scanning", path.to_string_lossy());
+            self.scan_dir(path, dir_entries, depth, stamp, walk)
+        };
+        for subdir in subdirs {
+            if let CheckerResult::Ok(child) = self.check_dir(&subdir, depth + 1, stamp, walk) {
+                report.issues.extend(child.issues);
+                report.errors.extend(child.errors);
+            }
+        }
+        walk.ignores.truncate(parent_ignores);
+        CheckerResult::Ok(report)
+    }
+
+    /// Reads a directory's entries, returning a report of its own entries and
+    /// the children directories to check next.
+    ///
+    /// Scans with unreadable entries are not cached, so they are tried again next time.
+    fn scan_dir(
+        &self,
+        path: &Path,
+        dir_entries: fs::ReadDir,
+        depth: usize,
+        stamp: u64,
+        walk: &mut Walk,
+    ) -> (Report, Vec<PathBuf>) {
         let mut issues = Vec::new();
-        for entry in dir_entries.flatten() {
-            if let Ok(Some(issue)) = self.rules.test_from_dir_entry(&entry) {
-                issues.push(issue);
-            }
-            if self.recursive && entry.file_type().ok().map_or(false, |ft| ft.is_dir()) {
-                if self
-                    .recursive_ignore_rules
-                    .matches_dir_entry(&entry)
-                    .ok()
-                    .unwrap_or(false)
-                {
+        let mut errors = Vec::new();
+        let mut subdirs = Vec::new();
+        for entry in dir_entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    debug!(
+                        "{}: an entry couldn't be read: {err}",
+                        path.to_string_lossy()
+                    );
+                    errors.push((path.to_path_buf(), err));
                     continue;
                 }
-                if let CheckerResult::Ok(report) = self.check_dir(&entry.path()) {
-                    issues.extend(report.issues);
+            };
+            if entry.file_name() == IGNORE_FILENAME
+                || (self.respect_gitignore && entry.file_name() == ".git")
+                || is_ignored(&walk.ignores, &entry, self.follow_symlinks)
+                || self
+                    .ignore_paths
+                    .iter()
+                    .any(|pattern| pattern.matches_path(&entry.path()))
+            {
+                debug!("{}: ignored", entry.path().to_string_lossy());
+                continue;
+            }
+            match self.test_entry(&entry) {
+                Ok(Some(issue)) => {
+                    debug!(
+                        "{}: misplaced, {}",
+                        entry.path().to_string_lossy(),
+                        self.explain(&issue)
+                    );
+                    issues.push(issue);
+                }
+                Ok(None) => debug!("{}: allowed", entry.path().to_string_lossy()),
+                Err(err) => {
+                    debug!(
+                        "{}: couldn't be checked: {err}",
+                        entry.path().to_string_lossy()
+                    );
+                    let err = err.downcast::<io::Error>().unwrap_or_else(io::Error::other);
+                    errors.push((entry.path(), err));
                 }
             }
+            if self.should_descend(&entry, depth, walk.root_device) {
+                subdirs.push(entry.path());
+            }
         }
-        CheckerResult::Ok(Report {
+        if let Some(cache) = walk.cache.as_deref_mut().filter(|_| errors.is_empty()) {
+            let cached_issues = issues
+                .iter()
+                .map(|issue| (issue.path.clone(), issue.reason))
+                .collect();
+            cache.insert(path, stamp, cached_issues, subdirs.clone());
+        }
+        let report = Report {
             path: path.to_path_buf(),
             issues,
+            errors,
+        };
+        (report, subdirs)
+    }
+
+    /// Returns an issue if an entry is misplaced
+    fn test_entry(&self, dir_entry: &DirEntry) -> anyhow::Result<Option<ReportIssue>> {
+        let reason = if self.disallowed_rules.matches_dir_entry(
+            dir_entry,
+            &self.path,
+            self.follow_symlinks,
+        )? {
+            IssueReason::Disallowed
+        } else if self
+            .rules
+            .matches_dir_entry(dir_entry, &self.path, self.follow_symlinks)?
+        {
+            return Ok(None);
+        } else if rules::is_broken_symlink(dir_entry) {
+            IssueReason::BrokenSymlink
+        } else {
+            IssueReason::NotAllowed
+        };
+        Ok(Some(ReportIssue {
+            path: dir_entry.path(),
+            metadata: crate::rules::resolve_metadata(dir_entry, self.follow_symlinks)?,
+            reason,
+        }))
+    }
+
+    /// Whether some rules look at the files themselves, whose changes the scan cache can't see
+    fn uses_file_state(&self) -> bool {
+        [
+            &self.rules,
+            &self.disallowed_rules,
+            &self.recursive_ignore_rules,
+        ]
+        .iter()
+        .any(|rule| rule.uses_file_state())
+    }
+
+    /// Whether some rules compare access times, but the directory is on a filesystem that
+    /// doesn't record them
+    pub fn access_times_unrecorded(&self) -> bool {
+        let rules = [
+            &self.rules,
+            &self.disallowed_rules,
+            &self.recursive_ignore_rules,
+        ];
+        rules.iter().any(|rule| rule.uses_access_time()) && !rules::records_access_times(&self.path)
+    }
+
+    /// Describes why a file reported by this checker is misplaced
+    pub fn explain(&self, issue: &ReportIssue) -> String {
+        match issue.reason {
+            IssueReason::Disallowed => {
+                return format!(
+                    "matches a disallowed rule [{}]",
+                    self.labels.disallowed.join(", ")
+                );
+            }
+            IssueReason::BrokenSymlink => {
+                return match fs::read_link(&issue.path) {
+                    Ok(target) => format!("broken symlink to {}", target.to_string_lossy()),
+                    Err(_) => "broken symlink".to_string(),
+                };
+            }
+            IssueReason::NotAllowed => (),
+        }
+        let (kind, allowed) = if issue.metadata.is_dir() {
+            ("directory", &self.labels.allowed_dirs)
+        } else if issue.metadata.is_file() {
+            ("file", &self.labels.allowed_files)
+        } else {
+            return "neither a file nor a directory".to_string();
+        };
+        match allowed {
+            Some(labels) if labels.is_empty() => format!("no {kind} is allowed"),
+            Some(labels) => format!("no allowed rule matched, expected [{}]", labels.join(", ")),
+            None => format!("no allowed rule matched, any {kind} was expected"),
+        }
+    }
+
+    /// Whether a child entry found at `depth` should be checked recursively
+    fn should_descend(&self, entry: &DirEntry, depth: usize, root_device: Option<u64>) -> bool {
+        if !self.recursive || self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            return false;
+        }
+        let is_dir = match entry.file_type() {
+            Ok(ft) if ft.is_symlink() => {
+                self.recursive_follow_symlinks
+                    && fs::metadata(entry.path()).is_ok_and(|md| md.is_dir())
+            }
+            Ok(ft) => ft.is_dir(),
+            Err(_) => false,
+        };
+        let other_filesystem = root_device
+            .is_some_and(|root| device_id(&entry.path()).is_some_and(|device| device != root));
+        let hidden = self.skip_hidden_dirs && entry.file_name().to_string_lossy().starts_with('.');
+        is_dir
+            && !hidden
+            && !other_filesystem
+            && !self
+                .recursive_ignore_rules
+                .matches_dir_entry(entry, &self.path, self.follow_symlinks)
+                .ok()
+                .unwrap_or(false)
+    }
+}
+
+/// Rebuilds cached issues with the current metadata of their files,
+/// returning `None` if one of them is gone or a symlink broke or was fixed since
+fn cached_issues(
+    cached: &[(PathBuf, IssueReason)],
+    follow_symlinks: bool,
+) -> Option<Vec<ReportIssue>> {
+    cached
+        .iter()
+        .map(|(path, reason)| {
+            let mut metadata = fs::symlink_metadata(path).ok()?;
+            let broken = *reason == IssueReason::BrokenSymlink;
+            if metadata.is_symlink() {
+                match fs::metadata(path) {
+                    Ok(target) if !broken => {
+                        if follow_symlinks {
+                            metadata = target;
+                        }
+                    }
+                    Err(err) if broken && err.kind() == io::ErrorKind::NotFound => (),
+                    _ => return None,
+                }
+            } else if broken {
+                return None;
+            }
+            Some(ReportIssue {
+                path: path.clone(),
+                metadata,
+                reason: *reason,
+            })
         })
+        .collect()
+}
+
+/// Returns the id of the device a file is on, always `None` on non-Unix systems
+fn device_id(path: &Path) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        fs::metadata(path).ok().map(|md| md.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
     }
 }
 
-impl FileMatchRule {
-    pub fn test_from_dir_entry(&self, dir_entry: &DirEntry) -> anyhow::Result<Option<ReportIssue>> {
-        if self.matches_dir_entry(dir_entry)? {
-            Ok(None)
-        } else {
-            Ok(Some(ReportIssue {
-                path: dir_entry.path(),
-                metadata: crate::rules::resolve_metadata(dir_entry)?,
-            }))
+/// Parses an ignore file (with the `.gitignore` syntax) of a directory, if there is one
+fn load_ignore_file(dir: &Path, filename: &str) -> Option<Gitignore> {
+    let path = dir.join(filename);
+    if !path.is_file() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(dir);
+    // invalid lines are skipped, the others still apply
+    let _ = builder.add(path);
+    builder.build().ok()
+}
+
+/// Whether an entry is excluded by ignore files, the deepest one that matches deciding
+fn is_ignored(ignores: &[Gitignore], entry: &DirEntry, follow_symlinks: bool) -> bool {
+    let path = entry.path();
+    let is_dir = crate::rules::resolve_metadata(entry, follow_symlinks).is_ok_and(|md| md.is_dir());
+    for ignore in ignores.iter().rev() {
+        match ignore.matched(&path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
         }
     }
+    false
 }
 
 impl CheckerResult {
@@ -178,44 +590,553 @@ impl ReportIssue {
     pub fn file_metadata(&self) -> &Metadata {
         &self.metadata
     }
-}
 
-/// Sets up a [`Checker`] from config
-pub fn from_config(config: &Config, parent: Option<PathBuf>) -> anyhow::Result<Checker> {
-    let mut directories = Vec::new();
-    for (dir_path, dir_config) in &config.directories {
-        let raw_path = shellexpand::env(dir_path)?;
-        let path = PathBuf::from(raw_path.as_ref());
+    pub fn reason(&self) -> IssueReason {
+        self.reason
+    }
 
-        let mut rules_dir = vec![FileMatchRule::Type(FileType::Directory)];
-        if let Some(rules) = &dir_config.allowed_dirs {
-            rules_dir.push(rules::compile_config_rules(rules)?);
+    pub fn kind(&self) -> IssueKind {
+        if self.reason == IssueReason::BrokenSymlink {
+            IssueKind::BrokenSymlink
+        } else if self.metadata.is_dir() {
+            IssueKind::Directory
+        } else if self.metadata.is_symlink() {
+            IssueKind::Symlink
+        } else {
+            IssueKind::File
         }
+    }
+}
 
-        let mut rules_file = vec![FileMatchRule::Type(FileType::File)];
-        if let Some(rules) = &dir_config.allowed_files {
-            rules_file.push(rules::compile_config_rules(rules)?);
+impl Checker {
+    /// Sets up a [`Checker`] from config, `parent` limiting runs to the directories under it
+    pub fn from_config(config: &Config, parent: Option<PathBuf>) -> anyhow::Result<Self> {
+        Self::from_config_filtered(config, parent, |_| true)
+    }
+
+    /// Like [`Checker::from_config`], but only sets up the directories whose name (config key)
+    /// is accepted by `include`
+    pub fn from_config_filtered(
+        config: &Config,
+        parent: Option<PathBuf>,
+        include: impl Fn(&str) -> bool,
+    ) -> anyhow::Result<Self> {
+        let mut directories = Vec::new();
+        for (dir_path, dir_config) in &config.directories {
+            if !dir_config.enabled || !include(dir_path) {
+                continue;
+            }
+            directories.push(directory_from_config(
+                dir_path,
+                dir_config,
+                &config.settings,
+            )?);
         }
 
-        // recursive ignore only applies on directories anyway, no need to ignore FileType::File here
-        let recursive_ignore_children =
-            rules::compile_config_rules(&dir_config.recursive_ignore_children)?;
+        directories.sort_by_cached_key(|dir| dir.path.clone());
+        Ok(Self {
+            parent,
+            directories,
+        })
+    }
+}
+
+/// Sets up a [`DirectoryChecker`] from the config of a directory
+pub fn directory_from_config(
+    dir_path: &str,
+    dir_config: &DirectoryConfig,
+    settings: &Settings,
+) -> anyhow::Result<DirectoryChecker> {
+    let raw_path = shellexpand::env(dir_path)?;
+    let path = PathBuf::from(raw_path.as_ref());
 
-        let rules = FileMatchRule::MergeOr(vec![
-            FileMatchRule::MergeAnd(rules_dir),
-            FileMatchRule::MergeAnd(rules_file),
-        ]);
-        directories.push(DirectoryChecker {
-            path,
-            recursive: dir_config.recursive,
-            recursive_ignore_rules: recursive_ignore_children,
+    let mut rules_dir = vec![FileMatchRule::Type(FileType::Directory)];
+    if let Some(rules) = &dir_config.allowed_dirs {
+        rules_dir.push(rules::compile_config_rules(
             rules,
-        });
+            dir_config.case_insensitive,
+        )?);
+    }
+
+    // broken symlinks have no file type to follow, so they are left to the rules for files
+    let mut rules_file = vec![FileMatchRule::MergeOr(vec![
+        FileMatchRule::Type(FileType::File),
+        FileMatchRule::Type(FileType::BrokenSymlink),
+    ])];
+    if let Some(rules) = &dir_config.allowed_files {
+        rules_file.push(rules::compile_config_rules(
+            rules,
+            dir_config.case_insensitive,
+        )?);
     }
 
-    directories.sort_by_cached_key(|dir| dir.path.clone());
-    Ok(Checker {
-        parent,
-        directories,
+    // recursive ignore only applies on directories anyway, no need to ignore FileType::File here
+    let recursive_ignore_children = rules::compile_config_rules(
+        &dir_config.recursive_ignore_children,
+        dir_config.case_insensitive,
+    )?;
+
+    let rules = FileMatchRule::MergeOr(vec![
+        FileMatchRule::MergeAnd(rules_dir),
+        FileMatchRule::MergeAnd(rules_file),
+    ]);
+    let disallowed_rules =
+        rules::compile_config_rules(&dir_config.disallowed, dir_config.case_insensitive)?;
+
+    let describe = |rules: &Vec<MatchRule>| rules.iter().map(ToString::to_string).collect();
+    let labels = RuleLabels {
+        allowed_dirs: dir_config.allowed_dirs.as_ref().map(describe),
+        allowed_files: dir_config.allowed_files.as_ref().map(describe),
+        disallowed: describe(&dir_config.disallowed),
+    };
+    Ok(DirectoryChecker {
+        name: dir_path.to_string(),
+        path,
+        recursive: dir_config.recursive,
+        max_depth: dir_config.max_depth,
+        recursive_ignore_rules: recursive_ignore_children,
+        recursive_follow_symlinks: dir_config.recursive_follow_symlinks,
+        skip_hidden_dirs: dir_config.skip_hidden_dirs,
+        one_filesystem: dir_config.one_filesystem,
+        respect_gitignore: dir_config.respect_gitignore,
+        color: dir_config.color,
+        unicode: dir_config.unicode,
+        cache: dir_config.cache,
+        follow_symlinks: dir_config
+            .follow_symlinks
+            .unwrap_or(settings.follow_symlinks),
+        ignore_paths: compile_ignore_paths(&settings.ignore_paths)?,
+        rules,
+        disallowed_rules,
+        labels,
     })
 }
+
+/// Compiles the `ignore-paths` setting, expanding `~` and environment variables
+fn compile_ignore_paths(paths: &[String]) -> anyhow::Result<Vec<glob::Pattern>> {
+    paths
+        .iter()
+        .map(|path| {
+            let expanded = shellexpand::full(path)?;
+            if !Path::new(expanded.as_ref()).is_absolute() {
+                anyhow::bail!("Path {path} of ignore-paths is not absolute");
+            }
+            glob::Pattern::new(&expanded)
+                .map_err(|err| anyhow::format_err!("Invalid pattern {path} in ignore-paths: {err}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) fn test_fixture(name: &str, files: &[&str]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("shinydir-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    for file in files {
+        let path = root.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+    root
+}
+
+#[cfg(test)]
+fn test_check(root: &Path, dir_config: &str) -> Vec<PathBuf> {
+    let config: Config = toml::from_str(&format!(
+        "[settings]\n[automove]\n[dir.{:?}]\n{dir_config}",
+        root.to_string_lossy()
+    ))
+    .unwrap();
+    let checker = Checker::from_config(&config, None).unwrap();
+    let CheckerResult::Ok(report) = checker.directories[0].check(None) else {
+        panic!("fixture directory could not be checked");
+    };
+    let mut paths = report
+        .issues
+        .iter()
+        .map(|issue| issue.path().strip_prefix(root).unwrap().to_path_buf())
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn test_max_depth() {
+    let root = test_fixture(
+        "max-depth",
+        &["x.bad", "a/y.bad", "a/b/z.bad", "a/b/ok.txt"],
+    );
+    let rules = "recursive = true\nallowed-files = [{ ext = \"txt\" }]\n";
+    assert_eq!(
+        vec![
+            PathBuf::from("a/b/z.bad"),
+            PathBuf::from("a/y.bad"),
+            PathBuf::from("x.bad")
+        ],
+        test_check(&root, rules)
+    );
+    assert_eq!(
+        vec![PathBuf::from("x.bad")],
+        test_check(&root, &format!("{rules}max-depth = 0"))
+    );
+    assert_eq!(
+        vec![PathBuf::from("a/y.bad"), PathBuf::from("x.bad")],
+        test_check(&root, &format!("{rules}max-depth = 1"))
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlink_loop() {
+    let root = test_fixture("symlink-loop", &["a/x.bad"]);
+    std::os::unix::fs::symlink(".", root.join("a/current")).unwrap();
+    let rules = "recursive = true\nrecursive-follow-symlinks = true\nallowed-files = []\n";
+    assert_eq!(vec![PathBuf::from("a/x.bad")], test_check(&root, rules));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_disallowed() {
+    let root = test_fixture("disallowed", &["a.txt", ".DS_Store", "Thumbs.db"]);
+    let rules = "disallowed = [{ name = \".DS_Store\" }, { name = \"Thumbs.db\" }]\n";
+    assert_eq!(
+        vec![PathBuf::from(".DS_Store"), PathBuf::from("Thumbs.db")],
+        test_check(&root, rules)
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_shinydirignore() {
+    let root = test_fixture(
+        "shinydirignore",
+        &["x.bad", "cache/y.bad", "a/z.bad", "a/w.bad"],
+    );
+    fs::write(root.join(IGNORE_FILENAME), "cache/\n").unwrap();
+    fs::write(root.join("a").join(IGNORE_FILENAME), "z.bad\n").unwrap();
+    fs::write(root.join(".gitignore"), "w.bad\n").unwrap();
+    let rules =
+        "recursive = true\nallowed-files = [{ ext = \"txt\" }, { name = \".gitignore\" }]\n";
+    assert_eq!(
+        vec![PathBuf::from("a/w.bad"), PathBuf::from("x.bad")],
+        test_check(&root, rules)
+    );
+    assert_eq!(
+        vec![PathBuf::from("x.bad")],
+        test_check(&root, &format!("{rules}respect-gitignore = true"))
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_ignore_paths() {
+    let root = test_fixture(
+        "ignore-paths",
+        &["x.bad", "keep.bad", "old/y.bad", "a/z.bad"],
+    );
+    let config: Config = toml::from_str(&format!(
+        "[settings]\nignore-paths = [{:?}, {:?}]\n\
+        [automove]\n[dir.{:?}]\nrecursive = true\nallowed-files = [{{ ext = \"txt\" }}]\n",
+        root.join("keep.bad").to_string_lossy(),
+        root.join("*/y.*").to_string_lossy(),
+        root.to_string_lossy()
+    ))
+    .unwrap();
+    let checker = Checker::from_config(&config, None).unwrap();
+    let CheckerResult::Ok(report) = checker.directories[0].check(None) else {
+        panic!("fixture directory could not be checked");
+    };
+    let mut paths = report
+        .issues
+        .iter()
+        .map(|issue| issue.path().strip_prefix(&root).unwrap().to_path_buf())
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(
+        vec![PathBuf::from("a/z.bad"), PathBuf::from("x.bad")],
+        paths
+    );
+
+    let relative: Config =
+        toml::from_str("[settings]\nignore-paths = [\"keep.bad\"]\n[automove]\n[dir.\"/tmp\"]\n")
+            .unwrap();
+    assert!(Checker::from_config(&relative, None).is_err());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_skip_hidden_dirs() {
+    let root = test_fixture(
+        "skip-hidden-dirs",
+        &[
+            "x.bad",
+            ".hidden/y.bad",
+            ".hidden/deep/z.bad",
+            "shown/w.bad",
+        ],
+    );
+    let rules = "recursive = true\nallowed-dirs = [{ name = \"shown\" }]\nallowed-files = [{ ext = \"txt\" }]\n";
+    assert_eq!(
+        vec![
+            PathBuf::from(".hidden"),
+            PathBuf::from(".hidden/deep"),
+            PathBuf::from(".hidden/deep/z.bad"),
+            PathBuf::from(".hidden/y.bad"),
+            PathBuf::from("shown/w.bad"),
+            PathBuf::from("x.bad")
+        ],
+        test_check(&root, rules)
+    );
+    // the hidden directory itself is still checked
+    assert_eq!(
+        vec![
+            PathBuf::from(".hidden"),
+            PathBuf::from("shown/w.bad"),
+            PathBuf::from("x.bad")
+        ],
+        test_check(&root, &format!("{rules}skip-hidden-dirs = true"))
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_scan_cache() {
+    let root = test_fixture("scan-cache", &["x.bad", "a/ok.txt", "a/y.bad"]);
+    let config: Config = toml::from_str(&format!(
+        "[settings]\n[automove]\n[dir.{:?}]\nrecursive = true\ncache = true\nallowed-files = [{{ ext = \"txt\" }}]\n",
+        root.to_string_lossy()
+    ))
+    .unwrap();
+    let checker = Checker::from_config(&config, None).unwrap();
+    let cache_dir = root.with_extension("config");
+    let mut cache = ScanCache::load(&cache_dir);
+    let check = |cache: &mut ScanCache| {
+        let CheckerResult::Ok(report) = checker.directories[0].check(Some(cache)) else {
+            panic!("fixture directory could not be checked");
+        };
+        let mut paths = report
+            .issues
+            .iter()
+            .map(|issue| issue.path().strip_prefix(&root).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    };
+    // directories modified too recently are never cached
+    let past = filetime::FileTime::from_unix_time(1_000_000_000, 0);
+    filetime::set_file_mtime(root.join("a"), past).unwrap();
+    assert_eq!(
+        check(&mut cache),
+        [PathBuf::from("a/y.bad"), "x.bad".into()]
+    );
+
+    // a change that keeps the mtime goes unnoticed
+    fs::write(root.join("a/z.bad"), "").unwrap();
+    filetime::set_file_mtime(root.join("a"), past).unwrap();
+    cache.save().unwrap();
+    let mut cache = ScanCache::load(&cache_dir);
+    assert_eq!(
+        check(&mut cache),
+        [PathBuf::from("a/y.bad"), "x.bad".into()]
+    );
+
+    filetime::set_file_mtime(
+        root.join("a"),
+        filetime::FileTime::from_unix_time(1_000_000_001, 0),
+    )
+    .unwrap();
+    assert_eq!(
+        check(&mut cache),
+        [PathBuf::from("a/y.bad"), "a/z.bad".into(), "x.bad".into()]
+    );
+    fs::remove_dir_all(&root).unwrap();
+    fs::remove_dir_all(&cache_dir).unwrap();
+}
+
+#[test]
+fn test_scan_cache_file_state() {
+    let root = test_fixture("scan-cache-file-state", &["a.txt"]);
+    let config: Config = toml::from_str(&format!(
+        "[settings]\n[automove]\n[dir.{:?}]\ncache = true\nallowed-files = [{{ empty = true }}]\n",
+        root.to_string_lossy()
+    ))
+    .unwrap();
+    let checker = Checker::from_config(&config, None).unwrap();
+    let cache_dir = root.with_extension("config");
+    let mut cache = ScanCache::load(&cache_dir);
+    let past = filetime::FileTime::from_unix_time(1_000_000_000, 0);
+    filetime::set_file_mtime(&root, past).unwrap();
+    let CheckerResult::Ok(report) = checker.directories[0].check(Some(&mut cache)) else {
+        panic!("fixture directory could not be checked");
+    };
+    assert!(report.issues.is_empty());
+
+    // filling the file keeps the directory's mtime, but the rule must see it
+    fs::write(root.join("a.txt"), "filled").unwrap();
+    filetime::set_file_mtime(&root, past).unwrap();
+    let CheckerResult::Ok(report) = checker.directories[0].check(Some(&mut cache)) else {
+        panic!("fixture directory could not be checked");
+    };
+    assert_eq!(
+        [root.join("a.txt")],
+        *report
+            .issues
+            .iter()
+            .map(|issue| issue.path().to_path_buf())
+            .collect::<Vec<_>>()
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_broken_symlinks() {
+    let root = test_fixture("broken-symlinks", &["a.txt", "b.bad"]);
+    std::os::unix::fs::symlink("missing.txt", root.join("broken.txt")).unwrap();
+    std::os::unix::fs::symlink("missing", root.join("broken")).unwrap();
+    let config: Config = toml::from_str(&format!(
+        "[settings]\n[automove]\n[dir.{:?}]\nallowed-files = [{{ ext = \"txt\" }}]\n",
+        root.to_string_lossy()
+    ))
+    .unwrap();
+    let checker = Checker::from_config(&config, None).unwrap();
+    let CheckerResult::Ok(report) = checker.directories[0].check(None) else {
+        panic!("fixture directory could not be checked");
+    };
+    assert!(report.errors.is_empty());
+    let mut issues = report
+        .issues
+        .iter()
+        .map(|issue| (issue.path().strip_prefix(&root).unwrap(), issue.reason()))
+        .collect::<Vec<_>>();
+    issues.sort_by_key(|(path, _)| *path);
+    assert_eq!(
+        vec![
+            (Path::new("b.bad"), IssueReason::NotAllowed),
+            (Path::new("broken"), IssueReason::BrokenSymlink)
+        ],
+        issues
+    );
+    let broken = report
+        .issues
+        .iter()
+        .find(|issue| issue.reason() == IssueReason::BrokenSymlink)
+        .unwrap();
+    assert_eq!(
+        "broken symlink to missing",
+        checker.directories[0].explain(broken)
+    );
+    assert_eq!(
+        vec![PathBuf::from("b.bad")],
+        test_check(
+            &root,
+            "allowed-files = [{ ext = \"txt\" }, { type = \"broken-symlink\" }]\n"
+        )
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_follow_symlinks() {
+    let root = test_fixture("follow-symlinks", &["sub/a.txt"]);
+    std::os::unix::fs::symlink("sub", root.join("link")).unwrap();
+    std::os::unix::fs::symlink("sub/a.txt", root.join("file-link.txt")).unwrap();
+    let rules = "allowed-files = [{ ext = \"txt\" }]\n";
+    assert!(test_check(&root, rules).is_empty());
+    let rules = format!("{rules}follow-symlinks = false");
+    assert_eq!(
+        vec![PathBuf::from("file-link.txt"), PathBuf::from("link")],
+        test_check(&root, &rules)
+    );
+
+    // reports list them as symlinks, rather than as neither files nor directories
+    let config: Config = toml::from_str(&format!(
+        "[settings]\n[automove]\n[dir.{:?}]\n{rules}",
+        root.to_string_lossy()
+    ))
+    .unwrap();
+    let checker = Checker::from_config(&config, None).unwrap();
+    let CheckerResult::Ok(report) = checker.directories[0].check(None) else {
+        panic!("fixture directory could not be checked");
+    };
+    assert!(report
+        .issues
+        .iter()
+        .all(|issue| issue.kind() == IssueKind::Symlink));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_all_rule() {
+    let root = test_fixture("all-rule", &["report-1.pdf", "report-2.txt", "notes.pdf"]);
+    let rules = "allowed-files = [{ all = [{ glob = \"report-*\" }, { ext = \"pdf\" }] }]\n";
+    assert_eq!(
+        vec![PathBuf::from("notes.pdf"), PathBuf::from("report-2.txt")],
+        test_check(&root, rules)
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_case_insensitive() {
+    let root = test_fixture(
+        "case-insensitive",
+        &["PHOTO.JPG", "photo.jpg", "README", "a.png"],
+    );
+    let rules = "allowed-files = [{ ext = \"jpg\" }, { name = \"readme\" }]\n";
+    assert_eq!(
+        vec![
+            PathBuf::from("PHOTO.JPG"),
+            PathBuf::from("README"),
+            PathBuf::from("a.png"),
+        ],
+        test_check(&root, rules)
+    );
+    assert_eq!(
+        vec![PathBuf::from("a.png")],
+        test_check(&root, &format!("case-insensitive = true\n{rules}"))
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_not_rule() {
+    let root = test_fixture("not-rule", &["screenshot-1.png", "photo.png", "notes.txt"]);
+    let rules = "allowed-files = [{ all = [{ ext = \"png\" }, { not = [{ glob = \"screenshot-*\" }] }] }]\n";
+    assert_eq!(
+        vec![
+            PathBuf::from("notes.txt"),
+            PathBuf::from("screenshot-1.png")
+        ],
+        test_check(&root, rules)
+    );
+    // nothing matches an empty list, so its negation allows everything
+    assert!(test_check(&root, "allowed-files = [{ not = [] }]\n").is_empty());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_path_pattern() {
+    let root = test_fixture(
+        "path-pattern",
+        &["photos/2023/a.jpg", "photos/b.jpg", "other/c.jpg"],
+    );
+    let rules =
+        "recursive = true\nallowed-files = [{ path-pattern = '^photos/2023/[^/]+\\.jpg$' }]\n";
+    assert_eq!(
+        vec![PathBuf::from("other/c.jpg"), PathBuf::from("photos/b.jpg")],
+        test_check(&root, rules)
+    );
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_accessed_older_than() {
+    let root = test_fixture("accessed-older-than", &["old.txt", "recent.txt"]);
+    let past = filetime::FileTime::from_unix_time(1_000_000_000, 0);
+    filetime::set_file_atime(root.join("old.txt"), past).unwrap();
+    let rules = "disallowed = [{ accessed-older-than = 30 }]\n";
+    assert_eq!(vec![PathBuf::from("old.txt")], test_check(&root, rules));
+    fs::remove_dir_all(root).unwrap();
+}
@@ -1,13 +1,25 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 pub struct Cli {
-    /// Custom config file to use
+    /// Custom config file to use, or `-` to read it from stdin
     #[arg(short, long, value_name = "FILE")]
     pub config: Option<PathBuf>,
 
+    /// When to use colors in the terminal output
+    #[arg(long, global = true, value_enum, value_name = "WHEN", default_value_t)]
+    pub color: ColorChoice,
+
+    /// Don't print informational messages and warnings, only results and errors
+    #[arg(short, long, global = true, action = clap::ArgAction::SetTrue)]
+    pub quiet: bool,
+
+    /// Log how each file is checked against rules on stderr (-vv to also log every rule)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -15,26 +27,310 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     /// Check configured directories and report misplaced files
-    Check {
-        /// Parent directory. Leave blank to check all configured directories
-        target: Option<PathBuf>,
-
-        /// Print the list of misplaced files (one per line) without additional formatting
-        #[arg(short, long, action = clap::ArgAction::SetTrue)]
-        list: bool,
-    },
+    Check(CheckArgs),
     /// Automatically move misplaced files according to set rules
     #[command(aliases = ["au", "aumove"])]
-    AutoMove {
-        /// Parent directory. Leave blank to check all configured directories
-        target: Option<PathBuf>,
+    AutoMove(AutoMoveArgs),
+    /// Move back the files moved by the last auto-move run
+    Undo {
+        /// Undo the latest run that wasn't undone yet, when the last run already was
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        previous: bool,
+    },
+    /// Check the config for errors without running anything
+    Validate,
+    /// Inspect the config
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Report misplaced files along with the moves auto-move would make, without moving anything
+    Report(ReportArgs),
+    /// Check directories, then move the misplaced files that auto-move rules apply to
+    Tidy(TidyArgs),
+    /// Create a config file, choosing which directories to check
+    Init(InitArgs),
+    /// Print a shell completion script
+    Completions {
+        /// Shell to generate the completion script for
+        shell: clap_complete::Shell,
+    },
+}
 
-        /// Print the list of files to move (one line per action, separated with a space) without additional formatting
-        #[arg(short, long, action = clap::ArgAction::SetTrue)]
-        list: bool,
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the directories and auto-move rules as set up from the config, with environment
+    /// variables expanded and rules compiled
+    Dump,
+}
 
-        /// Print files that would be affected without actually moving them
-        #[arg(id = "dry", short, long, action = clap::ArgAction::SetTrue)]
-        dry_run: bool,
-    },
+#[derive(Args, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct CheckArgs {
+    /// Parent directory. Leave blank to check all configured directories
+    pub target: Option<PathBuf>,
+
+    /// Read parent directories from stdin (one per line) instead of the target argument
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "target")]
+    pub stdin: bool,
+
+    /// Check the target directory with the given glob as allowed files instead of the config (repeatable)
+    #[arg(long, value_name = "GLOB", requires = "target")]
+    pub allow: Vec<String>,
+
+    /// Print the list of misplaced files (one per line) without additional formatting
+    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    pub list: bool,
+
+    /// Like --list, but end each file with a NUL character instead of a newline (for `xargs -0`)
+    #[arg(short = '0', long, action = clap::ArgAction::SetTrue)]
+    pub print0: bool,
+
+    /// Show the size of misplaced files, and the number of entries of misplaced directories
+    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    pub sizes: bool,
+
+    /// Show when misplaced files were last modified (as an ISO 8601 column with --list)
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "print0")]
+    pub show_mtime: bool,
+
+    /// Show why each file is misplaced, with the rules it was checked against
+    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    pub explain: bool,
+
+    /// Only show how many files are misplaced in each directory, not the files themselves
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["list", "print0", "count", "sizes", "show_mtime", "explain"])]
+    pub summary: bool,
+
+    /// Show every misplaced file of each directory (the default)
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "summary")]
+    pub full: bool,
+
+    /// Order in which directories are reported
+    #[arg(long, value_enum, default_value_t)]
+    pub sort: SortOrder,
+
+    /// Keep running and redraw the report whenever a checked directory changes
+    #[arg(short, long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["list", "print0"])]
+    pub watch: bool,
+
+    /// Only print the total number of misplaced files
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["list", "print0", "watch", "sizes", "show_mtime", "explain"])]
+    pub count: bool,
+
+    /// Only check the configured directory with the given name (config key, repeatable), like --only
+    #[arg(long, value_name = "NAME", conflicts_with = "allow")]
+    pub dir: Vec<String>,
+
+    /// Only report files that weren't misplaced in a baseline saved with `report --format json`
+    #[arg(long, value_name = "BASELINE", conflicts_with = "allow")]
+    pub since: Option<PathBuf>,
+
+    /// Also list the files of the baseline that are no longer misplaced
+    #[arg(long, action = clap::ArgAction::SetTrue, requires = "since", conflicts_with_all = ["list", "print0", "count"])]
+    pub show_resolved: bool,
+
+    /// Exit with an error status if any misplaced file is found
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "watch")]
+    pub fail_on_issues: bool,
+
+    /// Report how long each directory took to check, slowest first
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["list", "print0", "count"])]
+    pub timings: bool,
+
+    #[command(flatten)]
+    pub selection: Selection,
+}
+
+impl CheckArgs {
+    /// How much of each directory report to print
+    pub fn output_level(&self) -> OutputLevel {
+        if self.summary {
+            OutputLevel::Summary
+        } else {
+            OutputLevel::Full
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct ReportArgs {
+    /// Parent directory. Leave blank to report all configured directories and rules
+    pub target: Option<PathBuf>,
+
+    /// Output format of the report
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args)]
+pub struct TidyArgs {
+    /// Parent directory. Leave blank to tidy all configured directories
+    pub target: Option<PathBuf>,
+
+    /// Move the files without asking for confirmation
+    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    pub yes: bool,
+
+    /// Print files that would be moved without actually moving them
+    #[arg(id = "dry", short, long, action = clap::ArgAction::SetTrue)]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+pub struct InitArgs {
+    /// Overwrite the config file if it already exists
+    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    pub force: bool,
+
+    /// Check every suggested directory without asking
+    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    pub yes: bool,
+
+    /// Set of directories and rules to start from
+    #[arg(short, long, value_enum, default_value_t)]
+    pub preset: Preset,
+}
+
+#[derive(Args)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct AutoMoveArgs {
+    /// Parent directory. Leave blank to check all configured directories
+    pub target: Option<PathBuf>,
+
+    /// Print the list of files to move (one line per action, separated with a space) without additional formatting
+    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    pub list: bool,
+
+    /// Like --list, but end both paths of each action with a NUL character (for `xargs -0`)
+    #[arg(short = '0', long, action = clap::ArgAction::SetTrue, conflicts_with = "format")]
+    pub print0: bool,
+
+    /// Print files that would be affected without actually moving them
+    #[arg(id = "dry", short, long, action = clap::ArgAction::SetTrue)]
+    pub dry_run: bool,
+
+    /// Actually move files when `safe-mode` is on in the config, instead of doing a dry run
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["dry", "tree"])]
+    pub apply: bool,
+
+    /// Ask for confirmation before moving each file (ignored when stdout isn't a terminal)
+    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    pub interactive: bool,
+
+    /// Show where files would be moved as a tree of destination directories (implies --dry)
+    #[arg(short, long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["list", "print0", "format"])]
+    pub tree: bool,
+
+    /// List the moved files under each destination directory instead of only counting them
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["tree", "list", "print0", "format"])]
+    pub detailed: bool,
+
+    /// Output format of the report
+    #[arg(long, value_enum, default_value_t, conflicts_with = "list")]
+    pub format: OutputFormat,
+
+    /// Run even if some to-scripts are missing or not executable, reporting their files as errors
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub skip_missing_scripts: bool,
+
+    /// Move every file or none: if a move fails, the files already moved are moved back
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub transactional: bool,
+
+    #[command(flatten)]
+    pub selection: Selection,
+}
+
+/// Directories or rules to run, by name
+#[derive(Args, Clone, Debug, Default)]
+pub struct Selection {
+    /// Only run the directories (config key) or rules (name) with the given name
+    #[arg(long, value_name = "NAME")]
+    pub only: Vec<String>,
+
+    /// Skip the directories (config key) or rules (name) with the given name
+    #[arg(long, value_name = "NAME")]
+    pub skip: Vec<String>,
+}
+
+impl Selection {
+    /// Checks if a directory or rule is selected
+    pub fn includes(&self, name: &str) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|only| only == name))
+            && !self.skip.iter().any(|skip| skip == name)
+    }
+
+    /// Ensures every selected name exists
+    pub fn validate<'a, I>(&self, names: I) -> anyhow::Result<()>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let names = names.into_iter().collect::<Vec<_>>();
+        for name in self.only.iter().chain(&self.skip) {
+            if !names.contains(&name.as_str()) {
+                anyhow::bail!(
+                    "Unknown name '{name}'. Valid names are: {}",
+                    names.join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Format of a command's report
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable output
+    #[default]
+    Text,
+    /// Machine readable JSON
+    Json,
+}
+
+/// How much of each directory is printed in a check report
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputLevel {
+    /// Only the number of misplaced files
+    Summary,
+    /// Every misplaced file
+    #[default]
+    Full,
+}
+
+/// Order of the directories in a check report
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    /// Alphabetically by path
+    #[default]
+    Path,
+    /// Alphabetically by name in the config
+    Name,
+    /// Most misplaced files first
+    Count,
+}
+
+/// Config to start from with the init command
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// The bundled config, with a few commented out auto-move rules
+    #[default]
+    Default,
+    /// Documents, pictures, music and videos sorted out of the home directory
+    Home,
+    /// Camera files kept in the pictures directory
+    Photographer,
+}
+
+/// When to use colors in the terminal output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Use the config setting, or detect from `NO_COLOR` and the terminal if unset
+    #[default]
+    Auto,
+    /// Always use colors
+    Always,
+    /// Never use colors
+    Never,
 }
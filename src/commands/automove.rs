@@ -1,80 +1,136 @@
-use anyhow::Error;
+use anyhow::{bail, Error};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
 use std::path::{Path, PathBuf};
 
 use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
 
-use crate::automove::{AutoMoveResult, AutoMoveResultEntry, AutoMoveRule};
-use crate::config::{Config, Settings};
+use crate::automove::{
+    self, AutoMove, AutoMoveResult, AutoMoveResultEntry, AutoMoveRule, ConflictResolution,
+    EntryError,
+};
+use crate::cli::{AutoMoveArgs, OutputFormat, Selection};
+use crate::config::{Config, ConflictStrategy, MovedToOrder, Settings};
+use crate::journal::{Journal, JournalEntry};
+use crate::move_log::MoveLog;
+
+pub fn execute(config: &Config, config_dir: &Path, args: AutoMoveArgs) -> anyhow::Result<()> {
+    let AutoMoveArgs {
+        target,
+        list,
+        print0,
+        mut dry_run,
+        apply,
+        interactive,
+        tree,
+        detailed,
+        format,
+        skip_missing_scripts,
+        transactional,
+        selection,
+    } = args;
+    let list = list || print0;
 
-pub fn execute(
-    config: &Config,
-    config_dir: &Path,
-    target: Option<PathBuf>,
-    list: bool,
-    mut dry_run: bool,
-) -> anyhow::Result<()> {
     // Setup automove
     let parent = target.map(fs::canonicalize).transpose()?;
-    let automove = crate::automove::from_config(config, config_dir, parent)?;
-
-    automove.check_empty(config)?;
-    let script_warning = automove.script_warning(config);
-    dry_run_warning(config, &mut dry_run);
+    let automove = setup_automove(config, config_dir, parent, &selection, skip_missing_scripts)?;
+    let script_warning = script_warning(config, &automove);
+    let nested_warning = nested_destination_warning(config, &automove);
+    let noatime_warning = super::noatime_warning(
+        config,
+        automove
+            .rules
+            .iter()
+            .filter(|rule| rule.access_times_unrecorded())
+            .map(|rule| rule.directory.as_path()),
+    );
+    let safe_mode = config.automove.safe_mode && !apply && !dry_run && !tree;
+    dry_run |= tree || safe_mode;
+    let dry_run_info = dry_run_warning(config, &mut dry_run, safe_mode);
 
     let mut results = automove.run(); // Get entries to move
 
-    if (script_warning || dry_run) && !list {
+    let raw_output = list || format == OutputFormat::Json;
+    if (script_warning || nested_warning || noatime_warning || dry_run_info) && !raw_output {
         eprintln!(); // Print newline after info message
     }
 
     // Move files
-    for result in &mut results {
-        if let AutoMoveResult::Ok { entries, .. } = result {
-            process_automove_result_entry(config, dry_run, entries);
+    let mut confirm = if interactive && !raw_output && !dry_run && io::stdout().is_terminal() {
+        Confirm::Ask
+    } else {
+        Confirm::All
+    };
+    let transaction = transactional.then(Transaction::default);
+    let rolled_back = move_files(
+        config,
+        config_dir,
+        dry_run,
+        &mut confirm,
+        transaction,
+        raw_output,
+        &mut results,
+    )?;
+
+    if config.automove.notify && !dry_run {
+        send_notification(&results);
+    }
+
+    if format == OutputFormat::Json {
+        print_json(&results, dry_run)?;
+        return transaction_result(rolled_back);
+    }
+
+    if list {
+        for result in &results {
+            if let AutoMoveResult::Ok { entries, .. } = result {
+                print_list(entries, print0)?;
+            }
         }
+    } else {
+        let layout = if tree {
+            Layout::Tree
+        } else if detailed {
+            Layout::Detailed
+        } else {
+            Layout::Compact
+        };
+        print_results(config, &results, dry_run, layout);
     }
+    transaction_result(rolled_back)
+}
 
-    // Display output
+/// Prints the report of every rule, followed by the totals
+pub(crate) fn print_results(
+    config: &Config,
+    results: &[AutoMoveResult],
+    dry_run: bool,
+    layout: Layout,
+) {
     let mut first_entry = true;
     let mut hidden = 0;
     let mut any_move = false;
-    for result in &results {
+    for result in results {
         match result {
-            AutoMoveResult::DirDoesNotExist { rule } if !list => {
+            AutoMoveResult::DirDoesNotExist { rule } => {
                 if first_entry {
                     first_entry = false;
                 } else {
                     println!();
                 }
-                let display_name = if rule.custom_name.is_none() && config.settings.color {
-                    format!("{}", rule.display_name().italic())
-                } else {
-                    rule.display_name()
-                };
-                if config.settings.color {
-                    eprintln!("{} Directory does not exist!", display_name.red());
-                } else {
-                    eprintln!("{}: Directory does not exist!", display_name);
-                }
+                print_missing_dir(&config.settings, rule);
             }
-            AutoMoveResult::Ok { rule, entries } => {
-                if list {
-                    let line_entries = entries
-                        .iter()
-                        .filter_map(|entry| entry.as_ref().ok())
-                        .map(|entry| {
-                            format!(
-                                "{} {}",
-                                entry.file.to_string_lossy().replace(' ', "\\ "),
-                                entry.move_to.to_string_lossy().replace(' ', "\\ ")
-                            )
-                        })
-                        .collect::<Vec<_>>();
-                    if !line_entries.is_empty() {
-                        println!("{}", line_entries.join("\n"));
-                    }
-                } else if config.settings.hide_ok_directories && entries.is_empty() {
+            AutoMoveResult::Ok {
+                rule,
+                entries,
+                removed_dirs,
+                errors,
+            } => {
+                if config.settings.hide_ok_directories && entries.is_empty() && errors.is_empty() {
                     hidden += 1;
                 } else {
                     if first_entry {
@@ -82,15 +138,25 @@ pub fn execute(
                     } else {
                         println!();
                     }
-                    print_entries(&config.settings, rule, entries);
+                    print_entries(
+                        &config.settings,
+                        dry_run,
+                        layout,
+                        rule,
+                        entries,
+                        *removed_dirs,
+                        errors,
+                    );
                     any_move = true;
                 }
             }
-            AutoMoveResult::DirDoesNotExist { .. } => {}
-        };
+        }
     }
 
-    if hidden > 0 && !list {
+    if any_move {
+        print_total(&config.settings, results, dry_run);
+    }
+    if hidden > 0 && !config.settings.quiet {
         if hidden != results.len() {
             println!();
         }
@@ -98,24 +164,281 @@ pub fn execute(
     }
 
     if config.automove.force_dry_run && any_move {
-        if config.settings.color {
-            eprintln!("\n\n{}", "No files were actually moved as you are a new user. Please refer to the \"Info!\" note at the beginning of this output.".italic());
+        show_force_dry_run_reminder(config);
+    }
+}
+
+/// Reminds new users at the end of the output that nothing was moved
+fn show_force_dry_run_reminder(config: &Config) {
+    if config.settings.color {
+        eprintln!("\n\n{}", "No files were actually moved as you are a new user. Please refer to the \"Info!\" note at the beginning of this output.".italic());
+    } else {
+        eprintln!("\n\nNo files were actually moved as you are a new user. Please refer to the \"Info!\" note at the beginning of this output.");
+    }
+}
+
+/// Fails the command when a transaction was rolled back, after its output was printed
+fn transaction_result(rolled_back: bool) -> anyhow::Result<()> {
+    if rolled_back {
+        bail!("\nA move failed, so every file moved during this run was moved back");
+    }
+    Ok(())
+}
+
+/// Prints the actions of a rule without formatting, as space separated
+/// (or NUL terminated) source and destination paths
+fn print_list(
+    entries: &[Result<AutoMoveResultEntry, anyhow::Error>],
+    print0: bool,
+) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    for entry in entries
+        .iter()
+        .filter_map(|entry| entry.as_ref().ok())
+        .filter(|entry| !entry.is_skipped())
+    {
+        if print0 {
+            super::print_path0(&mut stdout, &entry.file)?;
+            super::print_path0(&mut stdout, &entry.move_to)?;
         } else {
-            eprintln!("\n\nNo files were actually moved as you are a new user. Please refer to the \"Info!\" note at the beginning of this output.");
+            writeln!(
+                stdout,
+                "{} {}",
+                entry.file.to_string_lossy().replace(' ', "\\ "),
+                entry.move_to.to_string_lossy().replace(' ', "\\ ")
+            )?;
         }
     }
-
     Ok(())
 }
 
+/// Moves the files of every rule, recording moves in a journal for undo.
+///
+/// With `transaction`, every move is undone as soon as one fails, and `true` is returned.
+/// A progress bar is shown unless the output is raw or not a terminal.
+pub(crate) fn move_files(
+    config: &Config,
+    config_dir: &Path,
+    dry_run: bool,
+    confirm: &mut Confirm,
+    mut transaction: Option<Transaction>,
+    raw_output: bool,
+    results: &mut [AutoMoveResult],
+) -> anyhow::Result<bool> {
+    let mut records = Records {
+        journal: Journal::new(config_dir),
+        log: if dry_run {
+            None
+        } else {
+            MoveLog::from_config(config, config_dir)?
+        },
+    };
+    let total = results
+        .iter()
+        .map(|result| match result {
+            AutoMoveResult::Ok { entries, .. } => entries.len(),
+            AutoMoveResult::DirDoesNotExist { .. } => 0,
+        })
+        .sum();
+    // prompts of interactive mode would be drawn over by the bar
+    let show_progress = !raw_output
+        && !config.settings.quiet
+        && *confirm != Confirm::Ask
+        && io::stdout().is_terminal();
+    let mut progress = Progress::new(total, show_progress);
+    if let Some(transaction) = &mut transaction {
+        // files that can't be moved at all fail the transaction before anything is moved
+        transaction.failed = results.iter().any(|result| match result {
+            AutoMoveResult::Ok { entries, .. } => entries.iter().any(Result::is_err),
+            AutoMoveResult::DirDoesNotExist { .. } => false,
+        });
+    }
+    for result in results.iter_mut() {
+        process_automove_result_entry(
+            config,
+            dry_run,
+            confirm,
+            &mut records,
+            &mut transaction,
+            &mut progress,
+            result,
+        );
+    }
+    progress.bar.finish_and_clear();
+    if let Some(transaction) = transaction.filter(|transaction| transaction.failed) {
+        roll_back(&transaction, &mut records, results)?;
+        return Ok(true);
+    }
+    for result in results {
+        if let AutoMoveResult::Ok {
+            rule,
+            entries,
+            removed_dirs,
+            ..
+        } = result
+        {
+            if config.automove.remove_empty_dirs {
+                let moved = entries
+                    .iter()
+                    .filter_map(|entry| entry.as_ref().ok())
+                    .filter(|entry| entry.is_source_gone())
+                    .map(|entry| entry.file.as_path())
+                    .collect::<Vec<_>>();
+                *removed_dirs = automove::remove_emptied_dirs(&rule.directory, &moved, dry_run);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Where moves are recorded: the journal used for undo, and the log file if enabled
+struct Records {
+    journal: Journal,
+    log: Option<MoveLog>,
+}
+
+/// Progress bar of a run, advanced once per processed entry
+struct Progress {
+    bar: ProgressBar,
+    errors: usize,
+}
+
+impl Progress {
+    fn new(total: usize, visible: bool) -> Self {
+        let bar = if visible {
+            ProgressBar::new(u64::try_from(total).unwrap_or(u64::MAX))
+        } else {
+            ProgressBar::hidden()
+        };
+        if let Ok(style) = ProgressStyle::with_template("{bar:30} {pos}/{len} {wide_msg}") {
+            bar.set_style(style.progress_chars("=> "));
+        }
+        Self { bar, errors: 0 }
+    }
+
+    /// Counts an entry as processed, showing the file it is about if known
+    fn advance(&mut self, file: Option<&Path>, failed: bool) {
+        if failed {
+            self.errors += 1;
+        }
+        let file = file
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        match self.errors {
+            0 => self.bar.set_message(file.into_owned()),
+            errors => self.bar.set_message(format!("({errors} errors) {file}")),
+        }
+        self.bar.inc(1);
+    }
+}
+
+/// Moves done by a `--transactional` run, undone if any move fails
+#[derive(Default)]
+pub(crate) struct Transaction {
+    /// Source and destination of the files moved so far, in order
+    completed: Vec<(PathBuf, PathBuf)>,
+    failed: bool,
+}
+
+/// Moves back every file moved by a failed transaction, the last moved first.
+///
+/// Files moved back are reported as skipped, and removed from the journal.
+fn roll_back(
+    transaction: &Transaction,
+    records: &mut Records,
+    results: &mut [AutoMoveResult],
+) -> anyhow::Result<()> {
+    let mut entries = results
+        .iter_mut()
+        .filter_map(|result| match result {
+            AutoMoveResult::Ok { entries, .. } => Some(entries),
+            AutoMoveResult::DirDoesNotExist { .. } => None,
+        })
+        .flat_map(|entries| entries.iter_mut())
+        .filter(|entry| entry.as_ref().is_ok_and(|entry| !entry.is_skipped()))
+        .collect::<Vec<_>>();
+    let mut kept = Vec::new();
+    for (from, to) in transaction.completed.iter().rev() {
+        let Some(entry_res) = entries.iter_mut().find(|entry| {
+            entry
+                .as_ref()
+                .is_ok_and(|entry| &entry.file == from && &entry.move_to == to)
+        }) else {
+            continue;
+        };
+        match automove::move_file(to, from, false) {
+            Ok(()) => {
+                if let Ok(entry) = entry_res.as_mut() {
+                    entry.declined = true;
+                }
+                if let Some(move_log) = &mut records.log {
+                    // the log is only best effort, as when recording moves
+                    let _ = move_log.record("moved back", to, from, None);
+                }
+            }
+            Err(err) => {
+                let err = anyhow::format_err!(
+                    "Couldn't move {} back to {}: {}",
+                    to.to_string_lossy(),
+                    from.to_string_lossy(),
+                    err
+                );
+                **entry_res = Err(EntryError::wrap(err, from.clone(), Some(to.clone())));
+                kept.push(JournalEntry {
+                    from: from.clone(),
+                    to: to.clone(),
+                });
+            }
+        }
+    }
+    kept.reverse();
+    records.journal.replace(&kept)
+}
+
+pub(crate) fn setup_automove(
+    config: &Config,
+    config_dir: &Path,
+    parent: Option<PathBuf>,
+    selection: &Selection,
+    skip_missing_scripts: bool,
+) -> anyhow::Result<AutoMove> {
+    let mut automove = AutoMove::from_config(config, config_dir, parent)?;
+    let mut rule_names = automove
+        .rules
+        .iter()
+        .map(AutoMoveRule::name)
+        .collect::<Vec<_>>();
+    rule_names.dedup();
+    selection.validate(rule_names.iter().map(String::as_str))?;
+    automove
+        .rules
+        .retain(|rule| selection.includes(&rule.name()));
+
+    if automove.rules.is_empty() {
+        let w = if config.settings.color {
+            "(!)".bold()
+        } else {
+            "(!)".into()
+        };
+        bail!("{w} No auto-move rules were configured.")
+    }
+    if !skip_missing_scripts {
+        automove.check_scripts().map_err(|err| {
+            anyhow::format_err!("{err} (use --skip-missing-scripts to run anyway)")
+        })?;
+    }
+    Ok(automove)
+}
+
 fn show_hidden_info(config: &Config, hidden: usize) {
     if config.settings.color {
         println!(
             "{} {}",
             if config.settings.unicode {
-                format!("\u{f00c} {} rules", hidden)
+                format!("{} {hidden} rules", config.settings.glyphs.checkmark)
             } else {
-                format!("{} rules", hidden)
+                format!("{hidden} rules")
             }
             .bright_white()
             .bold()
@@ -128,67 +451,549 @@ fn show_hidden_info(config: &Config, hidden: usize) {
         println!(
             "{} rules were hidden from the output (nothing to move)",
             if config.settings.unicode {
-                format!("\u{f00c} {}", hidden)
+                format!("{} {hidden}", config.settings.glyphs.checkmark)
             } else {
-                format!("{}", hidden)
+                format!("{hidden}")
             },
         );
     }
 }
 
+/// How many files are moved in parallel before being recorded in the journal
+const MOVE_CHUNK_SIZE: usize = 256;
+
+/// Whether to ask before moving files, as set by `--interactive`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Confirm {
+    /// Ask for every file
+    Ask,
+    /// Move every file without asking
+    All,
+    /// Move no more files
+    Quit,
+}
+
+impl Confirm {
+    /// Whether the move of an entry is approved, prompting for it if needed
+    fn approves(&mut self, entry: &AutoMoveResultEntry) -> anyhow::Result<bool> {
+        loop {
+            match self {
+                Confirm::All => return Ok(true),
+                Confirm::Quit => return Ok(false),
+                Confirm::Ask => {}
+            }
+            eprint!(
+                "move {} -> {}? [y/N/a/q] ",
+                entry.file.to_string_lossy(),
+                entry.move_to.to_string_lossy()
+            );
+            io::stderr().flush()?;
+            let mut answer = String::new();
+            if io::stdin().lock().read_line(&mut answer)? == 0 {
+                // Treat end of input like quitting
+                *self = Confirm::Quit;
+                continue;
+            }
+            match answer.trim().to_lowercase().as_str() {
+                "y" | "yes" => return Ok(true),
+                "" | "n" | "no" => return Ok(false),
+                "a" | "all" => *self = Confirm::All,
+                "q" | "quit" => *self = Confirm::Quit,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Whether a transaction is ongoing and one of its moves failed
+fn has_failed(transaction: Option<&Transaction>) -> bool {
+    transaction.is_some_and(|transaction| transaction.failed)
+}
+
 fn process_automove_result_entry(
     config: &Config,
     dry_run: bool,
-    entries: &mut Vec<Result<AutoMoveResultEntry, Error>>,
+    confirm: &mut Confirm,
+    records: &mut Records,
+    transaction: &mut Option<Transaction>,
+    progress: &mut Progress,
+    result: &mut AutoMoveResult,
 ) {
-    for entry_res in entries {
-        let entry = if let Ok(entry) = entry_res.as_ref() {
-            entry
-        } else {
+    let AutoMoveResult::Ok { rule, entries, .. } = result else {
+        return;
+    };
+    let log = &mut records.log;
+    // Conflicts and directories are dealt with one entry at a time, only the moves
+    // themselves (and the deletions conflicts call for) are done in parallel
+    let mut claimed = HashSet::new();
+    let mut pending = Vec::new();
+    for (i, entry_res) in entries.iter_mut().enumerate() {
+        let Ok(entry) = entry_res.as_mut() else {
+            progress.advance(None, true);
             continue;
         };
-        if !dry_run {
-            if let Some(parent) = entry.move_to.parent() {
-                if let Err(err) = fs::create_dir_all(parent) {
-                    *entry_res = Err(anyhow::format_err!(
-                        "Couldn't create directory {}: {}",
-                        parent.to_string_lossy(),
-                        err
-                    ));
-                    continue;
+        if has_failed(transaction.as_ref()) {
+            entry.declined = true;
+            progress.advance(Some(&entry.file), false);
+            continue;
+        }
+        match confirm.approves(entry) {
+            Ok(true) => {}
+            Ok(false) => {
+                entry.declined = true;
+                progress.advance(Some(&entry.file), false);
+                continue;
+            }
+            Err(err) => {
+                progress.advance(Some(&entry.file), true);
+                *entry_res = Err(entry_error(entry, err));
+                continue;
+            }
+        }
+        let transactional = transaction.is_some();
+        match prepare_entry(config, rule, dry_run, transactional, entry, &mut claimed) {
+            Ok(Some(action)) if !dry_run => pending.push((i, action)),
+            Ok(_) => {
+                log_entry(log, entry, None);
+                progress.advance(Some(&entry.file), false);
+            }
+            Err(err) => {
+                log_entry(log, entry, Some(&err));
+                progress.advance(Some(&entry.file), true);
+                *entry_res = Err(entry_error(entry, err));
+                if let Some(transaction) = transaction {
+                    transaction.failed = true;
                 }
             }
         }
-        let new_err = match entry.move_to.try_exists() {
-            Ok(true) if !config.automove.allow_overwrite => Some(anyhow::format_err!(
-                "Moving to {} would overwrite a file",
-                entry.move_to.to_string_lossy()
-            )),
-            Err(err) => Some(anyhow::format_err!(
-                "Cannot check overwrite status for {}: {}",
-                entry.move_to.to_string_lossy(),
-                err
-            )),
-            _ if !dry_run => fs::rename(&entry.file, &entry.move_to)
-                .map_err(|err| {
-                    anyhow::format_err!(
-                        "Couldn't move {} to {}: {}",
+    }
+
+    // Moves are recorded after each chunk so an interrupted run still leaves a usable journal
+    for chunk in pending.chunks(MOVE_CHUNK_SIZE) {
+        if has_failed(transaction.as_ref()) {
+            for &(i, _) in chunk {
+                if let Ok(entry) = entries[i].as_mut() {
+                    entry.declined = true;
+                    progress.advance(Some(&entry.file), false);
+                }
+            }
+            continue;
+        }
+        let moves = chunk
+            .iter()
+            .filter_map(|&(i, action)| Some((entries[i].as_ref().ok()?, action)))
+            .collect::<Vec<_>>();
+        let results = move_chunk(rule, &moves);
+        for (&(i, _), result) in chunk.iter().zip(results) {
+            let Ok(entry) = entries[i].as_ref() else {
+                continue;
+            };
+            if let Err(err) = result {
+                log_entry(log, entry, Some(&err));
+                progress.advance(Some(&entry.file), true);
+                entries[i] = Err(entry_error(entry, err));
+                if let Some(transaction) = transaction {
+                    transaction.failed = true;
+                }
+                continue;
+            }
+            log_entry(log, entry, None);
+            progress.advance(Some(&entry.file), false);
+            if entry.is_skipped() {
+                continue;
+            }
+            if let Some(transaction) = transaction {
+                transaction
+                    .completed
+                    .push((entry.file.clone(), entry.move_to.clone()));
+            }
+            if let Err(err) = records.journal.record(&entry.file, &entry.move_to) {
+                let err = anyhow::format_err!(
+                    "Moved {} but couldn't record it for undo: {}",
+                    entry.file.to_string_lossy(),
+                    err
+                );
+                entries[i] = Err(entry_error(entry, err));
+            }
+        }
+    }
+}
+
+/// What the move phase does with a prepared entry, once its file is known to be unchanged
+#[derive(Clone, Copy)]
+enum Action {
+    /// Moves the file, never overwriting one that appears at its destination in the meantime
+    Move,
+    /// Moves the file over the one at its destination
+    Replace,
+    /// Sends the file at the destination to the trash, then moves the file
+    TrashAndMove,
+    /// Deletes the file, identical to the one at its destination
+    RemoveIdentical,
+}
+
+/// Carries out the actions of a chunk of entries in parallel. Entries whose file changed
+/// since it was listed are left in place, as other processes may have touched the files
+/// meanwhile, and nothing is trashed or deleted for them.
+fn move_chunk(
+    rule: &AutoMoveRule,
+    moves: &[(&AutoMoveResultEntry, Action)],
+) -> Vec<anyhow::Result<()>> {
+    let unchanged = rule.recheck(&moves.iter().map(|(entry, _)| *entry).collect::<Vec<_>>());
+    let moves = moves
+        .iter()
+        .zip(unchanged)
+        .map(|(&(entry, action), unchanged)| (entry, action, unchanged))
+        .collect::<Vec<_>>();
+    automove::parallel_map(&moves, |&(entry, action, unchanged)| {
+        if !unchanged {
+            bail!(
+                "{} changed since it was listed, it was left in place",
+                entry.file.to_string_lossy()
+            );
+        }
+        let replace = match action {
+            Action::Move => false,
+            Action::Replace => true,
+            Action::TrashAndMove => {
+                if let Err(err) = trash::delete(&entry.move_to) {
+                    bail!(
+                        "Couldn't send {} to the trash: {}",
+                        entry.move_to.to_string_lossy(),
+                        err
+                    );
+                }
+                false
+            }
+            Action::RemoveIdentical => {
+                if let Err(err) = fs::remove_file(&entry.file) {
+                    bail!(
+                        "Couldn't delete {} (identical to {}): {}",
                         entry.file.to_string_lossy(),
                         entry.move_to.to_string_lossy(),
                         err
-                    )
-                })
-                .err(),
-            _ => None,
+                    );
+                }
+                return Ok(());
+            }
+        };
+        automove::move_file(&entry.file, &entry.move_to, replace).map_err(|err| {
+            anyhow::format_err!(
+                "Couldn't move {} to {}: {}",
+                entry.file.to_string_lossy(),
+                entry.move_to.to_string_lossy(),
+                err
+            )
+        })
+    })
+}
+
+/// Records the outcome of an entry in the log, giving up on logging if it can't be written
+fn log_entry(log: &mut Option<MoveLog>, entry: &AutoMoveResultEntry, err: Option<&Error>) {
+    let Some(move_log) = log else {
+        return;
+    };
+    let outcome = match err {
+        Some(_) => "error",
+        None if entry.is_skipped() => "skipped",
+        None => "moved",
+    };
+    let details = err.map(ToString::to_string);
+    if let Err(err) = move_log.record(outcome, &entry.file, &entry.move_to, details.as_deref()) {
+        eprintln!("Could not write to the log file, no more moves will be logged: {err}");
+        *log = None;
+    }
+}
+
+/// Prepares a single entry to be moved, creating its directory and resolving
+/// conflicts according to config.
+///
+/// `claimed` holds destinations already used during this run, whose files are only moved
+/// there later on: another entry going to one of them is renamed with "rename" and an error
+/// otherwise, without looking at the filesystem. In a transaction, conflicts that would
+/// delete or overwrite a file are errors as they couldn't be undone.
+///
+/// Nothing is deleted here, the returned action is left to the move phase, if any.
+fn prepare_entry(
+    config: &Config,
+    rule: &AutoMoveRule,
+    dry_run: bool,
+    transactional: bool,
+    entry: &mut AutoMoveResultEntry,
+    claimed: &mut HashSet<PathBuf>,
+) -> anyhow::Result<Option<Action>> {
+    if !dry_run {
+        if let Some(parent) = entry.move_to.parent() {
+            if let Err(err) = fs::create_dir_all(parent) {
+                bail!(
+                    "Couldn't create directory {}: {}",
+                    parent.to_string_lossy(),
+                    err
+                );
+            }
+        }
+    }
+    let is_taken = |path: &Path| -> anyhow::Result<bool> {
+        if claimed.contains(path) {
+            return Ok(true);
+        }
+        path.try_exists().map_err(|err| {
+            anyhow::format_err!(
+                "Cannot check overwrite status for {}: {}",
+                path.to_string_lossy(),
+                err
+            )
+        })
+    };
+    let strategy = config.automove.on_conflict;
+    if claimed.contains(&entry.move_to) && strategy != ConflictStrategy::Rename {
+        bail!(
+            "Moving to {} would overwrite another file moved there during this run",
+            entry.move_to.to_string_lossy()
+        );
+    }
+    let mut action = Action::Move;
+    if is_taken(&entry.move_to)? {
+        match strategy {
+            ConflictStrategy::Rename => {
+                let template = &config.automove.rename_template;
+                let hash8 = if template.contains("{hash8}") {
+                    automove::short_hash(&entry.file).map_err(|err| {
+                        anyhow::format_err!("Cannot hash {}: {}", entry.file.to_string_lossy(), err)
+                    })?
+                } else {
+                    String::new()
+                };
+                let Some(free) =
+                    automove::conflict_free_path(&entry.move_to, template, &hash8, is_taken)?
+                else {
+                    bail!(
+                        "Moving to {} would overwrite a file, and rename-template gives no other name",
+                        entry.move_to.to_string_lossy()
+                    );
+                };
+                entry.move_to = free;
+            }
+            ConflictStrategy::Error
+                if transactional || !may_overwrite(config, rule, &entry.move_to)? =>
+            {
+                bail!(
+                    "Moving to {} would overwrite a file",
+                    entry.move_to.to_string_lossy()
+                )
+            }
+            ConflictStrategy::Error => action = Action::Replace,
+            ConflictStrategy::Trash if transactional => bail!(
+                "Moving to {} would send the existing file to the trash, which can't be undone",
+                entry.move_to.to_string_lossy()
+            ),
+            ConflictStrategy::Trash => {
+                entry.resolution = Some(ConflictResolution::Trashed);
+                action = Action::TrashAndMove;
+            }
+            ConflictStrategy::KeepNewer => {
+                if is_newer_than_destination(entry)? {
+                    if transactional {
+                        bail!(
+                            "Moving to {} would replace an older file, which can't be undone",
+                            entry.move_to.to_string_lossy()
+                        );
+                    }
+                    entry.resolution = Some(ConflictResolution::ReplacedOlder);
+                    action = Action::Replace;
+                } else {
+                    entry.resolution = Some(ConflictResolution::KeptNewer);
+                    return Ok(None);
+                }
+            }
+            ConflictStrategy::SkipIdentical => {
+                let keep_source = config.automove.keep_identical_source || transactional;
+                return skip_identical(keep_source, entry);
+            }
+        }
+    }
+    claimed.insert(entry.move_to.clone());
+    Ok(Some(action))
+}
+
+/// Whether the file at `path` may be overwritten: any file with `allow-overwrite`, otherwise
+/// files matching `overwrite-if`
+fn may_overwrite(config: &Config, rule: &AutoMoveRule, path: &Path) -> anyhow::Result<bool> {
+    if config.automove.allow_overwrite {
+        return Ok(true);
+    }
+    rule.may_overwrite(path).map_err(|err| {
+        anyhow::format_err!(
+            "Cannot check overwrite status for {}: {}",
+            path.to_string_lossy(),
+            err
+        )
+    })
+}
+
+/// Leaves out an entry identical to the file at its destination, to be deleted unless
+/// `keep-identical-source` is set. Other files can't be moved without overwriting.
+fn skip_identical(
+    keep_source: bool,
+    entry: &mut AutoMoveResultEntry,
+) -> anyhow::Result<Option<Action>> {
+    let identical = automove::is_identical(&entry.file, &entry.move_to).map_err(|err| {
+        anyhow::format_err!(
+            "Cannot compare {} with {}: {}",
+            entry.file.to_string_lossy(),
+            entry.move_to.to_string_lossy(),
+            err
+        )
+    })?;
+    if !identical {
+        bail!(
+            "Moving to {} would overwrite a different file",
+            entry.move_to.to_string_lossy()
+        );
+    }
+    if keep_source {
+        entry.resolution = Some(ConflictResolution::KeptIdentical);
+        return Ok(None);
+    }
+    entry.resolution = Some(ConflictResolution::RemovedIdentical);
+    Ok(Some(Action::RemoveIdentical))
+}
+
+/// Compares the modification time of an entry with the file at its destination
+fn is_newer_than_destination(entry: &AutoMoveResultEntry) -> anyhow::Result<bool> {
+    let cannot_compare = |err: std::io::Error| {
+        anyhow::format_err!(
+            "Cannot compare {} with {}: {}",
+            entry.file.to_string_lossy(),
+            entry.move_to.to_string_lossy(),
+            err
+        )
+    };
+    let source_modified = entry.file_metadata.modified().map_err(cannot_compare)?;
+    let destination_modified = fs::metadata(&entry.move_to)
+        .and_then(|md| md.modified())
+        .map_err(cannot_compare)?;
+    Ok(source_modified > destination_modified)
+}
+
+/// Attaches the paths of an entry to an error about it
+fn entry_error(entry: &AutoMoveResultEntry, err: anyhow::Error) -> anyhow::Error {
+    EntryError::wrap(err, entry.file.clone(), Some(entry.move_to.clone()))
+}
+
+/// A move as printed in the JSON report
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    rule: String,
+    source: Option<&'a Path>,
+    destination: Option<&'a Path>,
+    /// One of "planned", "moved", "skipped" or "error"
+    status: &'static str,
+    error: Option<String>,
+}
+
+fn print_json(results: &[AutoMoveResult], dry_run: bool) -> anyhow::Result<()> {
+    let mut json_entries = Vec::new();
+    for result in results {
+        let AutoMoveResult::Ok {
+            rule,
+            entries,
+            errors,
+            ..
+        } = result
+        else {
+            continue;
         };
-        if let Some(err) = new_err {
-            *entry_res = Err(err);
+        for (path, err) in errors {
+            json_entries.push(JsonEntry {
+                rule: rule.display_name(),
+                source: Some(path),
+                destination: None,
+                status: "error",
+                error: Some(format!("Could not read entry: {err}")),
+            });
         }
+        for entry in entries {
+            json_entries.push(match entry {
+                Ok(entry) => JsonEntry {
+                    rule: rule.display_name(),
+                    source: Some(&entry.file),
+                    destination: Some(&entry.move_to),
+                    status: if entry.is_skipped() {
+                        "skipped"
+                    } else if dry_run {
+                        "planned"
+                    } else {
+                        "moved"
+                    },
+                    error: None,
+                },
+                Err(err) => {
+                    let paths = err.downcast_ref::<EntryError>();
+                    JsonEntry {
+                        rule: rule.display_name(),
+                        source: paths.map(|paths| paths.file.as_path()),
+                        destination: paths.and_then(|paths| paths.move_to.as_deref()),
+                        status: "error",
+                        error: Some(err.to_string()),
+                    }
+                }
+            });
+        }
+    }
+    println!("{}", serde_json::to_string_pretty(&json_entries)?);
+    Ok(())
+}
+
+///Warn user about slow execution time
+fn script_warning(config: &Config, automove: &AutoMove) -> bool {
+    let script_warning = config.automove.script_warning
+        && !config.settings.quiet
+        && automove.rules.iter().any(|rule| rule.to_script.is_some());
+    if script_warning {
+        // print on stderr to not affect pipe input (e.g. when using --list)
+        if config.settings.color {
+            eprintln!("{} Your auto-move rules are configured to call scripts {}. If execution time gets too long, {}.", "Heads up!".bright_red().bold(), "(to-script)".white().dimmed(), "scripts are the cause".bold());
+        } else {
+            eprintln!("Heads up! Your auto-move rules are configured to call scripts (to-script). If execution time gets too long, scripts are the cause.");
+        }
+    }
+    script_warning
+}
+
+/// Warns about rules moving files to a subdirectory of their `parent`, which repeated
+/// runs could pick up again
+fn nested_destination_warning(config: &Config, automove: &AutoMove) -> bool {
+    let nested_rules = automove
+        .rules
+        .iter()
+        .filter(|rule| rule.has_nested_destination())
+        .map(AutoMoveRule::display_name)
+        .collect::<Vec<_>>();
+    if nested_rules.is_empty() || config.settings.quiet {
+        return false;
+    }
+    // print on stderr to not affect pipe input (e.g. when using --list)
+    if config.settings.color {
+        eprintln!(
+            "{} These auto-move rules move files inside their parent directory, make sure moved files don't match again: {}",
+            "Heads up!".bright_red().bold(),
+            nested_rules.join(", ").bold()
+        );
+    } else {
+        eprintln!(
+            "Heads up! These auto-move rules move files inside their parent directory, make sure moved files don't match again: {}",
+            nested_rules.join(", ")
+        );
     }
+    true
 }
 
-/// Warn user about dry run
-fn dry_run_warning(config: &Config, dry_run: &mut bool) {
+/// Warn user about dry run, returning whether a message was printed.
+///
+/// The `force-dry-run` notice is shown even with `--quiet`, so it can't be missed.
+/// With `safe_mode`, the dry run is due to `safe-mode` and the notice tells how to move files.
+pub(crate) fn dry_run_warning(config: &Config, dry_run: &mut bool, safe_mode: bool) -> bool {
     if config.automove.force_dry_run {
         *dry_run = true;
         if config.settings.color {
@@ -201,23 +1006,138 @@ fn dry_run_warning(config: &Config, dry_run: &mut bool) {
         } else {
             eprintln!("INFO! Dry run is enabled for newly copied configs as a security measure. Turn off 'force-dry-run' in the config file to disable this security. Until then, no file will actually be moved!");
         }
-    } else if *dry_run {
+        true
+    } else if *dry_run && !config.settings.quiet {
+        let apply_hint = if safe_mode {
+            " Use --apply to move them, as safe-mode is on."
+        } else {
+            ""
+        };
         if config.settings.color {
             eprintln!(
-                "{} Auto-move running in {}, no files will actually be moved.",
+                "{} Auto-move running in {}, no files will actually be moved.{apply_hint}",
                 "Info!".bright_blue().bold(),
                 "dry mode".white().bold()
             );
         } else {
-            eprintln!("INFO! Auto-move running in dry mode, no files will actually be moved.");
+            eprintln!(
+                "INFO! Auto-move running in dry mode, no files will actually be moved.{apply_hint}"
+            );
+        }
+        true
+    } else {
+        false
+    }
+}
+
+/// How many entries of a rule were moved, skipped or errored
+#[derive(Default)]
+struct EntryCounts {
+    moved: usize,
+    /// Total size in bytes of the moved files
+    size: u64,
+    skipped: usize,
+    errors: usize,
+}
+
+impl EntryCounts {
+    fn of(entries: &[Result<AutoMoveResultEntry, anyhow::Error>]) -> Self {
+        let mut counts = Self::default();
+        for entry in entries {
+            match entry {
+                Ok(entry) if entry.is_skipped() => counts.skipped += 1,
+                Ok(entry) => {
+                    counts.moved += 1;
+                    counts.size += entry.file_metadata.len();
+                }
+                Err(_) => counts.errors += 1,
+            }
         }
+        counts
+    }
+
+    /// Sums the counts of every rule
+    fn total(results: &[AutoMoveResult]) -> Self {
+        let mut total = Self::default();
+        for result in results {
+            if let AutoMoveResult::Ok { entries, .. } = result {
+                let counts = Self::of(entries);
+                total.moved += counts.moved;
+                total.size += counts.size;
+                total.skipped += counts.skipped;
+                total.errors += counts.errors;
+            }
+        }
+        total
+    }
+}
+
+/// How moved files are described, as nothing is moved in a dry run
+fn moved_verb(dry_run: bool) -> &'static str {
+    if dry_run {
+        "would be moved"
+    } else {
+        "moved"
+    }
+}
+
+fn print_missing_dir(settings: &Settings, rule: &AutoMoveRule) {
+    let display_name = if rule.custom_name.is_none() && settings.color {
+        format!("{}", rule.display_name().italic())
+    } else {
+        rule.display_name()
+    };
+    if settings.color {
+        eprintln!("{} Directory does not exist!", display_name.red());
+    } else {
+        eprintln!("{display_name}: Directory does not exist!");
     }
 }
 
+/// Prints how many files were (or would be, in a dry run) moved across all rules and their
+/// total size
+fn print_total(settings: &Settings, results: &[AutoMoveResult], dry_run: bool) {
+    let EntryCounts { moved, size, .. } = EntryCounts::total(results);
+    let msg = format!(
+        "Total: {moved} files {} ({})",
+        moved_verb(dry_run),
+        super::human_size(size)
+    );
+    if settings.color {
+        println!("\n{}", msg.bright_white().bold());
+    } else {
+        println!("\n{msg}");
+    }
+}
+
+/// Sends a desktop notification summarizing the run, ignoring an unavailable notification backend
+pub(crate) fn send_notification(results: &[AutoMoveResult]) {
+    let EntryCounts { moved, errors, .. } = EntryCounts::total(results);
+    let _ = notify_rust::Notification::new()
+        .summary("shinydir auto-move")
+        .body(&format!("{moved} files moved, {errors} errors"))
+        .show();
+}
+
+/// How the destinations of a rule's moved files are shown
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Layout {
+    /// Destination directories with how many files went in each
+    Compact,
+    /// Destination directories each followed by the files that went in it, with `--detailed`
+    Detailed,
+    /// Destinations as a tree, with `--tree`
+    Tree,
+}
+
 fn print_entries(
     settings: &Settings,
+    dry_run: bool,
+    layout: Layout,
     rule: &AutoMoveRule,
     entries: &[Result<AutoMoveResultEntry, anyhow::Error>],
+    removed_dirs: usize,
+    read_errors: &[(PathBuf, io::Error)],
 ) {
     let display_name = if rule.custom_name.is_none() && settings.color {
         format!("{}", rule.display_name().italic())
@@ -225,31 +1145,58 @@ fn print_entries(
         rule.display_name()
     };
 
-    if entries.is_empty() {
-        let checkmark = if settings.unicode { "\u{f00c}" } else { "OK" };
+    if entries.is_empty() && read_errors.is_empty() {
+        let checkmark = if settings.unicode {
+            &settings.glyphs.checkmark
+        } else {
+            "OK"
+        };
         if settings.color {
             println!("{} {}", display_name.blue(), checkmark.green().bold());
         } else {
-            println!("{} {}", display_name, checkmark);
+            println!("{display_name} {checkmark}");
         }
         return;
     }
 
-    let valid_entries = entries.iter().filter(|entry| entry.is_ok()).count();
-    let errors = entries.iter().filter(|entry| entry.is_err()).count();
+    let EntryCounts {
+        moved: valid_entries,
+        size,
+        skipped,
+        errors,
+    } = EntryCounts::of(entries);
 
-    let dot = if settings.unicode { "\u{f444}" } else { "-" };
+    let dot = if settings.unicode {
+        &settings.glyphs.dot
+    } else {
+        "-"
+    };
     let mut info = Vec::new();
     if valid_entries > 0 {
-        let msg = format!("{} files moved", valid_entries);
+        let msg = format!(
+            "{valid_entries} files {} ({})",
+            moved_verb(dry_run),
+            super::human_size(size)
+        );
         if settings.color {
             info.push(format!("{}", msg.bright_yellow()));
         } else {
             info.push(msg);
         }
     }
+    if skipped > 0 {
+        info.push(format!("{skipped} skipped"));
+    }
     if errors > 0 {
-        let msg = format!("{} errors", errors);
+        let msg = format!("{errors} errors");
+        if settings.color {
+            info.push(format!("{}", msg.bright_red()));
+        } else {
+            info.push(msg);
+        }
+    }
+    if !read_errors.is_empty() {
+        let msg = format!("{} unreadable", read_errors.len());
         if settings.color {
             info.push(format!("{}", msg.bright_red()));
         } else {
@@ -271,20 +1218,75 @@ fn print_entries(
     let moved_to_dirs_no_dedup = entries
         .iter()
         .filter_map(|entry| entry.as_ref().ok())
+        .filter(|entry| !entry.is_skipped())
         .filter_map(|entry| entry.move_to.parent())
         .map(std::path::Path::to_path_buf)
         .collect::<Vec<_>>();
-    let mut moved_to_dirs = moved_to_dirs_no_dedup.clone();
-    moved_to_dirs.sort();
-    moved_to_dirs.dedup();
+    let moved_to_dirs = sorted_moved_to_dirs(settings.moved_to_order, &moved_to_dirs_no_dedup);
 
-    if moved_to_dirs.is_empty() {
-        for err in entries.iter().filter_map(|entry| entry.as_ref().err()) {
-            eprintln!("{}", format!("{}", err).bright_red().italic());
+    match layout {
+        Layout::Tree => print_tree(settings, entries),
+        _ if moved_to_dirs.is_empty() => {}
+        Layout::Detailed => print_moved_to_detailed(settings, rule, &moved_to_dirs, entries),
+        Layout::Compact => {
+            print_moved_to(settings, rule, &moved_to_dirs, &moved_to_dirs_no_dedup);
+        }
+    }
+
+    print_resolutions(settings, dry_run, entries);
+    if removed_dirs > 0 {
+        let action = if dry_run { "would remove" } else { "removed" };
+        let msg = format!("{action} {removed_dirs} empty directories");
+        if settings.color {
+            println!("{}", msg.dimmed());
+        } else {
+            println!("{msg}");
         }
-        return;
     }
 
+    print_errors(rule, entries, read_errors);
+}
+
+/// Prints the entries of a rule that failed or couldn't be read, on stderr
+fn print_errors(
+    rule: &AutoMoveRule,
+    entries: &[Result<AutoMoveResultEntry, anyhow::Error>],
+    read_errors: &[(PathBuf, io::Error)],
+) {
+    for err in entries.iter().filter_map(|entry| entry.as_ref().err()) {
+        eprintln!("{}", format!("{err}").bright_red().italic());
+    }
+    for (path, err) in read_errors {
+        let rel_path = path.strip_prefix(&rule.directory).unwrap_or(path);
+        let msg = format!(
+            "Could not read {}, it was not considered: {err}",
+            rel_path.to_string_lossy()
+        );
+        eprintln!("{}", msg.bright_red().italic());
+    }
+}
+
+/// Deduplicates the directories files were moved to, in the configured order
+fn sorted_moved_to_dirs(order: MovedToOrder, moved_to_dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut sorted = moved_to_dirs.to_vec();
+    sorted.sort();
+    sorted.dedup();
+    if order == MovedToOrder::Count {
+        // stable, so directories with as many files stay sorted by path
+        sorted.sort_by_cached_key(|path| {
+            Reverse(moved_to_dirs.iter().filter(|&dir| dir == path).count())
+        });
+    }
+    sorted
+}
+
+/// Prints the directories files were moved to, with how many files went in each
+fn print_moved_to(
+    settings: &Settings,
+    rule: &AutoMoveRule,
+    moved_to_dirs: &[PathBuf],
+    moved_to_dirs_no_dedup: &[PathBuf],
+) {
     let arrow = "=>";
     let rel_dirs_it = moved_to_dirs
         .iter()
@@ -302,7 +1304,7 @@ fn print_entries(
                 format!(
                     "{} {}",
                     path.to_string_lossy().bright_blue(),
-                    format!("({})", count).dimmed()
+                    format!("({count})").dimmed()
                 )
             })
             .collect::<Vec<_>>();
@@ -318,8 +1320,308 @@ fn print_entries(
             .collect::<Vec<_>>();
         println!("{} Moved To: {}", arrow, tmp.join(", "));
     }
+}
 
-    for err in entries.iter().filter_map(|entry| entry.as_ref().err()) {
-        eprintln!("{}", format!("{}", err).bright_red().italic());
+/// Prints the directories files were moved to, each followed by the files that went in it
+fn print_moved_to_detailed(
+    settings: &Settings,
+    rule: &AutoMoveRule,
+    moved_to_dirs: &[PathBuf],
+    entries: &[Result<AutoMoveResultEntry, anyhow::Error>],
+) {
+    if settings.color {
+        println!("{} {}", "=>".black(), "Moved To".bright_white().bold());
+    } else {
+        println!("=> Moved To:");
+    }
+    for dir in moved_to_dirs {
+        let files = entries
+            .iter()
+            .filter_map(|entry| entry.as_ref().ok())
+            .filter(|entry| !entry.is_skipped() && entry.move_to.parent() == Some(dir))
+            .collect::<Vec<_>>();
+        let rel_dir = dir.strip_prefix(&rule.directory).unwrap_or(dir);
+        let count = format!("({})", files.len());
+        if settings.color {
+            println!(
+                "  {} {}",
+                rel_dir.to_string_lossy().bright_blue(),
+                count.dimmed()
+            );
+        } else {
+            println!("  {} {count}", rel_dir.to_string_lossy());
+        }
+        for entry in files {
+            let from = entry.file.file_name().unwrap_or_default().to_string_lossy();
+            let to = entry
+                .move_to
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+            match (from == to, settings.color) {
+                (true, _) => println!("    {to}"),
+                (false, true) => println!("    {to} {}", format!("(was {from})").dimmed()),
+                (false, false) => println!("    {to} (was {from})"),
+            }
+        }
+    }
+}
+
+/// A directory or file of the destination tree
+#[derive(Default)]
+struct TreeNode {
+    /// Source filename, for files whose name changes when moved
+    renamed_from: Option<String>,
+    children: BTreeMap<String, TreeNode>,
+}
+
+/// Prints the destinations of moved files as a tree, from their closest common directory
+fn print_tree(settings: &Settings, entries: &[Result<AutoMoveResultEntry, anyhow::Error>]) {
+    let moved = entries
+        .iter()
+        .filter_map(|entry| entry.as_ref().ok())
+        .filter(|entry| !entry.is_skipped())
+        .collect::<Vec<_>>();
+    let Some(mut root) = moved.first().and_then(|entry| entry.move_to.parent()) else {
+        return;
+    };
+    while let Some(parent) = root.parent() {
+        if moved.iter().all(|entry| entry.move_to.starts_with(root)) {
+            break;
+        }
+        root = parent;
+    }
+
+    let mut tree = TreeNode::default();
+    for entry in moved {
+        let Ok(rel_path) = entry.move_to.strip_prefix(root) else {
+            continue;
+        };
+        let mut node = &mut tree;
+        for component in rel_path {
+            node = node
+                .children
+                .entry(component.to_string_lossy().to_string())
+                .or_default();
+        }
+        if entry.file.file_name() != entry.move_to.file_name() {
+            node.renamed_from = entry
+                .file
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string());
+        }
     }
+
+    if settings.color {
+        println!("{}", root.to_string_lossy().bright_blue());
+    } else {
+        println!("{}", root.to_string_lossy());
+    }
+    print_tree_children(settings, &tree, "");
+}
+
+fn print_tree_children(settings: &Settings, node: &TreeNode, indent: &str) {
+    let (branch, last_branch, pipe) = if settings.unicode {
+        (
+            "\u{251c}\u{2500}\u{2500} ",
+            "\u{2514}\u{2500}\u{2500} ",
+            "\u{2502}   ",
+        )
+    } else {
+        ("|-- ", "`-- ", "|   ")
+    };
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let is_last = i == node.children.len() - 1;
+        let prefix = format!("{indent}{}", if is_last { last_branch } else { branch });
+        let renamed_from = child
+            .renamed_from
+            .as_ref()
+            .map(|from| format!(" (from {from})"))
+            .unwrap_or_default();
+        if settings.color {
+            let name = if child.children.is_empty() {
+                name.normal()
+            } else {
+                name.bright_blue()
+            };
+            println!("{}{name}{}", prefix.dimmed(), renamed_from.dimmed());
+        } else {
+            println!("{prefix}{name}{renamed_from}");
+        }
+        let child_indent = format!("{indent}{}", if is_last { "    " } else { pipe });
+        print_tree_children(settings, child, &child_indent);
+    }
+}
+
+/// Prints how conflicts with existing files were resolved
+fn print_resolutions(
+    settings: &Settings,
+    dry_run: bool,
+    entries: &[Result<AutoMoveResultEntry, anyhow::Error>],
+) {
+    for entry in entries.iter().filter_map(|entry| entry.as_ref().ok()) {
+        let destination = entry.move_to.to_string_lossy();
+        let msg = match (entry.resolution, dry_run) {
+            (None, _) => continue,
+            (Some(ConflictResolution::Trashed), true) => format!("would trash {destination}"),
+            (Some(ConflictResolution::Trashed), false) => format!("trashed {destination}"),
+            (Some(ConflictResolution::ReplacedOlder), true) => {
+                format!("would overwrite older {destination}")
+            }
+            (Some(ConflictResolution::ReplacedOlder), false) => {
+                format!("overwrote older {destination}")
+            }
+            (Some(ConflictResolution::KeptNewer), true) => format!(
+                "would skip {} ({destination} is newer)",
+                entry.file.to_string_lossy()
+            ),
+            (Some(ConflictResolution::KeptNewer), false) => format!(
+                "skipped {} ({destination} is newer)",
+                entry.file.to_string_lossy()
+            ),
+            (Some(ConflictResolution::RemovedIdentical), true) => format!(
+                "would delete {} (identical to {destination})",
+                entry.file.to_string_lossy()
+            ),
+            (Some(ConflictResolution::RemovedIdentical), false) => format!(
+                "deleted {} (identical to {destination})",
+                entry.file.to_string_lossy()
+            ),
+            (Some(ConflictResolution::KeptIdentical), true) => format!(
+                "would skip {} (identical to {destination})",
+                entry.file.to_string_lossy()
+            ),
+            (Some(ConflictResolution::KeptIdentical), false) => format!(
+                "skipped {} (identical to {destination})",
+                entry.file.to_string_lossy()
+            ),
+        };
+        if settings.color {
+            println!("{}", msg.dimmed());
+        } else {
+            println!("{msg}");
+        }
+    }
+}
+
+#[test]
+fn test_shared_destination() {
+    for strategy in ["error", "rename", "trash", "keep-newer", "skip-identical"] {
+        let root = super::test_fixture("shared-dest", &["src/a.txt", "src/b.txt"]);
+        fs::write(root.join("src/a.txt"), "a").unwrap();
+        fs::write(root.join("src/b.txt"), "b").unwrap();
+        let config: Config = toml::from_str(&format!(
+            "[settings]\n[dir]\n[automove]\non-conflict = {strategy:?}\n\
+            [[automove.rules]]\nparent = {:?}\nto = {:?}\nmatch = [{{ ext = \"txt\" }}]\n\
+            rename = {{ from = \"^.*$\", to-name = \"f.txt\" }}\n",
+            root.join("src").to_string_lossy(),
+            root.join("dst").to_string_lossy(),
+        ))
+        .unwrap();
+        let automove = crate::automove::AutoMove::from_config(&config, &root, None).unwrap();
+        let mut results = automove.run();
+        move_files(
+            &config,
+            &root,
+            false,
+            &mut Confirm::All,
+            None,
+            true,
+            &mut results,
+        )
+        .unwrap();
+
+        let AutoMoveResult::Ok { entries, .. } = &results[0] else {
+            panic!("rule directory is missing");
+        };
+        let moved = fs::read_to_string(root.join("dst/f.txt")).unwrap();
+        if strategy == "rename" {
+            assert!(entries.iter().all(Result::is_ok));
+            let renamed = fs::read_to_string(root.join("dst/f (1).txt")).unwrap();
+            assert_ne!(moved, renamed);
+        } else {
+            // the second file is an error, and the first one stays where it was moved
+            let errors = entries
+                .iter()
+                .filter_map(|entry| entry.as_ref().err())
+                .collect::<Vec<_>>();
+            assert_eq!(1, errors.len(), "{strategy}");
+            assert!(errors[0]
+                .to_string()
+                .ends_with("would overwrite another file moved there during this run"));
+            let left = fs::read_dir(root.join("src")).unwrap().count();
+            assert_eq!(1, left, "{strategy}");
+            assert!(
+                !root.join(format!("src/{moved}.txt")).exists(),
+                "{strategy}"
+            );
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_transaction_roll_back() {
+    use std::os::unix::fs::MetadataExt;
+
+    let root = super::test_fixture("roll-back", &["a/a.txt", "b/b.txt", "c/c.txt", "blocker"]);
+    // tmpfs, so moves there are copies when the temporary directory is on another filesystem
+    let other_device =
+        Path::new("/dev/shm").join(format!("shinydir-roll-back-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&other_device);
+    if fs::create_dir_all(&other_device).is_err()
+        || fs::metadata(&other_device).unwrap().dev() == fs::metadata(&root).unwrap().dev()
+    {
+        let _ = fs::remove_dir_all(&other_device);
+        fs::remove_dir_all(root).unwrap();
+        return;
+    }
+    let config: Config = toml::from_str(&format!(
+        "[settings]\n[dir]\n[automove]\n\
+        [[automove.rules]]\nparent = {:?}\nto = {:?}\nmatch = [{{ ext = \"txt\" }}]\n\
+        [[automove.rules]]\nparent = {:?}\nto = {:?}\nmatch = [{{ ext = \"txt\" }}]\n\
+        [[automove.rules]]\nparent = {:?}\nto = {:?}\nmatch = [{{ ext = \"txt\" }}]\n",
+        root.join("a").to_string_lossy(),
+        root.join("dst").to_string_lossy(),
+        root.join("b").to_string_lossy(),
+        other_device.to_string_lossy(),
+        root.join("c").to_string_lossy(),
+        // a file is in the way, so the destination directory can't be created
+        root.join("blocker/dst").to_string_lossy(),
+    ))
+    .unwrap();
+    let automove = crate::automove::AutoMove::from_config(&config, &root, None).unwrap();
+    let mut results = automove.run();
+    let rolled_back = move_files(
+        &config,
+        &root,
+        false,
+        &mut Confirm::All,
+        Some(Transaction::default()),
+        true,
+        &mut results,
+    )
+    .unwrap();
+
+    assert!(rolled_back);
+    for file in ["a/a.txt", "b/b.txt", "c/c.txt"] {
+        assert!(root.join(file).exists(), "{file}");
+    }
+    assert!(!root.join("dst/a.txt").exists());
+    assert!(!other_device.join("b.txt").exists());
+    let moved_back = results
+        .iter()
+        .filter_map(|result| match result {
+            AutoMoveResult::Ok { entries, .. } => Some(entries),
+            AutoMoveResult::DirDoesNotExist { .. } => None,
+        })
+        .flatten()
+        .filter(|entry| entry.as_ref().is_ok_and(|entry| entry.declined))
+        .count();
+    assert_eq!(2, moved_back);
+    // both moves were recorded then removed, which leaves no journal behind
+    assert!(crate::journal::latest(&root).unwrap().is_none());
+    fs::remove_dir_all(other_device).unwrap();
+    fs::remove_dir_all(root).unwrap();
 }
@@ -1,87 +1,483 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
 use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
 use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
+use anyhow::{bail, format_err};
+use chrono::{DateTime, Local, SecondsFormat};
 use colored::Colorize;
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::Deserialize;
 
-use crate::checker::{CheckerResult, Report, ReportIssue};
-use crate::config::{AutoMoveReportInfo, Config, Settings};
+use crate::checker::{Checker, CheckerResult, DirectoryChecker, IssueKind, Report, ReportIssue};
+use crate::cli::{CheckArgs, OutputLevel, Selection, SortOrder};
+use crate::config::{AutoMoveReportInfo, Config, DirectoryConfig, MatchRule, Settings};
+use crate::scan_cache::ScanCache;
 
-pub fn execute(
+/// Delay to wait for changes to settle before re-running the check in watch mode
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Number of directories highlighted as the slowest ones by `--timings`
+const SLOWEST_HIGHLIGHTED: usize = 3;
+
+pub fn execute(config: &Config, config_dir: &Path, args: &CheckArgs) -> anyhow::Result<()> {
+    // Setup checker
+    let targets = if args.stdin {
+        read_targets(io::stdin().lock())?
+    } else {
+        args.target
+            .clone()
+            .map(fs::canonicalize)
+            .transpose()?
+            .into_iter()
+            .collect()
+    };
+    let selection = Selection {
+        only: [args.selection.only.as_slice(), &args.dir].concat(),
+        skip: args.selection.skip.clone(),
+    };
+    let checker = setup_checker(config, &selection, &targets, &args.allow, args.sort)?;
+    let baseline = args.since.as_deref().map(Baseline::load).transpose()?;
+    let baseline = baseline.as_ref();
+
+    if args.watch {
+        return watch(config, config_dir, &checker, baseline, &targets, args);
+    }
+    let misplaced = if args.count {
+        let misplaced = count(config, config_dir, &checker, baseline)?;
+        println!("{misplaced}");
+        misplaced
+    } else {
+        report(config, config_dir, &checker, baseline, &targets, args)?
+    };
+    if args.fail_on_issues && misplaced > 0 {
+        bail!("Found {misplaced} misplaced files");
+    }
+    Ok(())
+}
+
+/// Counts the misplaced files of every checked directory
+fn count(
     config: &Config,
     config_dir: &Path,
-    target: Option<PathBuf>,
-    list: bool,
+    checker: &Checker,
+    baseline: Option<&Baseline>,
+) -> io::Result<usize> {
+    let mut misplaced = 0;
+    run_checker(config, config_dir, checker, baseline, |_, result, _| {
+        if let CheckerResult::Ok(report) = result {
+            misplaced += report.issues.len();
+        }
+        Ok(())
+    })?;
+    Ok(misplaced)
+}
+
+/// Re-runs the check and redraws its report whenever a checked directory changes
+fn watch(
+    config: &Config,
+    config_dir: &Path,
+    checker: &Checker,
+    baseline: Option<&Baseline>,
+    targets: &[PathBuf],
+    args: &CheckArgs,
 ) -> anyhow::Result<()> {
-    // Setup checker
-    let parent = target.map(fs::canonicalize).transpose()?;
-    let checker = crate::checker::from_config(config, parent.clone())?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    for directory in &checker.directories {
+        let mode = if directory.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        match watcher.watch(&directory.path, mode) {
+            Ok(()) => {}
+            // Missing directories are already shown in the report
+            Err(err) if is_not_found(&err) => {}
+            Err(err) => bail!(
+                "Could not watch {}: {err}",
+                directory.path.to_string_lossy()
+            ),
+        }
+    }
+
+    let clear = io::stdout().is_terminal();
+    loop {
+        if clear {
+            // Clear the terminal and move the cursor back to the top
+            print!("\x1B[2J\x1B[1;1H");
+        }
+        report(config, config_dir, checker, baseline, targets, args)?;
+        loop {
+            match rx.recv() {
+                // Checking directories reads them, which must not trigger another check
+                Ok(Ok(event)) if matches!(event.kind, EventKind::Access(_)) => {}
+                Ok(Ok(_)) => break,
+                Ok(Err(err)) => bail!("Could not watch directories: {err}"),
+                Err(_) => return Ok(()),
+            }
+        }
+        // Wait for changes to settle, then check once for all of them
+        thread::sleep(WATCH_DEBOUNCE);
+        while rx.try_recv().is_ok() {}
+    }
+}
+
+/// Whether a watch failed because the directory doesn't exist
+fn is_not_found(err: &notify::Error) -> bool {
+    match &err.kind {
+        notify::ErrorKind::PathNotFound => true,
+        notify::ErrorKind::Io(err) => err.kind() == io::ErrorKind::NotFound,
+        _ => false,
+    }
+}
+
+/// Runs the checker once and prints its results, returning how many files are misplaced
+fn report(
+    config: &Config,
+    config_dir: &Path,
+    checker: &Checker,
+    baseline: Option<&Baseline>,
+    targets: &[PathBuf],
+    args: &CheckArgs,
+) -> anyhow::Result<usize> {
+    let printer = print_directories(config, config_dir, checker, baseline, args, false)?;
+    let footer_sep = print_footer(config, &printer, args.list || args.print0);
+
+    // Automove info
+    if !config.settings.quiet {
+        let all_hidden = printer.printed == printer.hidden;
+        show_automove_info(config, config_dir, targets, footer_sep, all_hidden)?;
+    }
+    Ok(printer.summary.misplaced)
+}
+
+/// Runs the checker once and prints its results, returning the paths of the misplaced files
+pub(crate) fn report_misplaced(
+    config: &Config,
+    config_dir: &Path,
+    checker: &Checker,
+    args: &CheckArgs,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let printer = print_directories(config, config_dir, checker, None, args, true)?;
+    print_footer(config, &printer, args.list || args.print0);
+    Ok(printer.misplaced.unwrap_or_default())
+}
+
+/// Prints the report of every checked directory, with the paths of misplaced files
+/// kept if `keep_misplaced` is set.
+///
+/// Directories are printed as soon as they are checked, unless they are sorted by count.
+fn print_directories<'a>(
+    config: &'a Config,
+    config_dir: &Path,
+    checker: &Checker,
+    baseline: Option<&Baseline>,
+    args: &'a CheckArgs,
+    keep_misplaced: bool,
+) -> anyhow::Result<ReportPrinter<'a>> {
+    let mut printer = ReportPrinter {
+        config,
+        args,
+        first_entry: true,
+        printed: 0,
+        hidden: 0,
+        summary: Summary::default(),
+        misplaced: keep_misplaced.then(Vec::new),
+        resolved: Vec::new(),
+        timings: Vec::new(),
+    };
+    if args.sort == SortOrder::Count {
+        let mut results = Vec::new();
+        printer.resolved = run_checker(
+            config,
+            config_dir,
+            checker,
+            baseline,
+            |directory, result, elapsed| {
+                results.push((directory, result, elapsed));
+                Ok(())
+            },
+        )?;
+        results.sort_by_key(|(_, result, _)| match result {
+            CheckerResult::Ok(report) => Reverse(report.issues.len()),
+            _ => Reverse(0),
+        });
+        for (directory, result, elapsed) in results {
+            printer.print(directory, &result, elapsed)?;
+        }
+    } else {
+        let resolved = run_checker(
+            config,
+            config_dir,
+            checker,
+            baseline,
+            |directory, result, elapsed| printer.print(directory, &result, elapsed),
+        )?;
+        printer.resolved = resolved;
+    }
+    colored::control::set_override(config.settings.color);
+    Ok(printer)
+}
+
+/// Prints the hidden directories and the summary, unless the output is a list.
+///
+/// Returns whether anything was printed.
+fn print_footer(config: &Config, printer: &ReportPrinter, list: bool) -> bool {
+    if list {
+        return false;
+    }
+    if printer.printed != printer.hidden {
+        println!();
+    }
+    if printer.hidden > 0 && !config.settings.quiet {
+        show_hidden_info(config, printer.hidden);
+    }
+    if printer.args.show_resolved {
+        print_resolved(&config.settings, &printer.resolved);
+    }
+    if printer.args.timings {
+        print_timings(&config.settings, &printer.timings);
+    }
+    printer.summary.print(&config.settings);
+    true
+}
 
-    checker.check_empty(config)?;
+/// Prints the result of each checked directory, keeping track of what the footer needs
+struct ReportPrinter<'a> {
+    config: &'a Config,
+    args: &'a CheckArgs,
+    first_entry: bool,
+    /// Directories handed to the printer, including hidden ones
+    printed: usize,
+    hidden: usize,
+    summary: Summary,
+    /// Paths of the misplaced files, if they are kept
+    misplaced: Option<Vec<PathBuf>>,
+    /// Files of the baseline that are no longer misplaced
+    resolved: Vec<PathBuf>,
+    /// How long each directory took to check, if timings are shown
+    timings: Vec<(PathBuf, Duration)>,
+}
 
-    // Run & display results
-    let results = checker.run();
-    let results_len = results.len();
+impl ReportPrinter<'_> {
+    fn print(
+        &mut self,
+        directory: &DirectoryChecker,
+        result: &CheckerResult,
+        elapsed: Duration,
+    ) -> io::Result<()> {
+        let CheckArgs {
+            list,
+            print0,
+            sizes,
+            show_mtime,
+            explain,
+            ..
+        } = *self.args;
+        let list = list || print0;
+        self.printed += 1;
+        if self.args.timings {
+            self.timings.push((directory.path.clone(), elapsed));
+        }
 
-    let mut first_entry = true;
-    let mut hidden = 0;
-    for result in results {
+        let settings = directory.settings(&self.config.settings);
+        colored::control::set_override(settings.color);
         if let CheckerResult::Ok(report) = result {
+            self.summary.add(report);
+            if let Some(misplaced) = &mut self.misplaced {
+                misplaced.extend(report.issues.iter().map(|issue| issue.path().to_path_buf()));
+            }
             if list {
-                let abs_files = report
-                    .issues
-                    .iter()
-                    .map(|issue| issue.path().to_string_lossy())
-                    .collect::<Vec<_>>();
-                if abs_files.is_empty() {
-                    continue;
-                }
-                println!("{}", abs_files.join("\n"));
-            } else if config.settings.hide_ok_directories && report.issues.is_empty() {
-                hidden += 1;
+                print_list(report, print0, show_mtime)?;
+            } else if self.config.settings.hide_ok_directories
+                && report.issues.is_empty()
+                && report.errors.is_empty()
+            {
+                self.hidden += 1;
             } else {
-                if first_entry {
-                    first_entry = false;
-                } else {
-                    println!();
+                self.separate();
+                print_report(
+                    &settings,
+                    report,
+                    self.args.output_level(),
+                    sizes,
+                    show_mtime,
+                );
+                if explain {
+                    print_explanations(&settings, directory, report);
                 }
-                print_report(&config.settings, &report);
+                print_read_errors(&settings, report, explain);
             }
         } else if !list {
-            if first_entry {
-                first_entry = false;
-            } else {
-                println!();
-            }
-            if config.settings.color {
-                eprintln!(
-                    "{} {}",
-                    result.path().to_string_lossy().red(),
-                    result.format_err()
-                );
-            } else {
-                eprintln!(
-                    "{}: {}",
-                    result.path().to_string_lossy(),
-                    result.format_err()
-                );
-            }
+            self.separate();
+            print_error(&settings, result);
         }
+        Ok(())
     }
 
-    let mut footer_sep = false;
-    if hidden > 0 {
-        footer_sep = true;
-        if results_len != hidden {
+    /// Prints an empty line between directories
+    fn separate(&mut self) {
+        if self.first_entry {
+            self.first_entry = false;
+        } else {
             println!();
         }
-        show_hidden_info(config, hidden);
     }
+}
 
-    // Automove info
-    let automove = crate::automove::from_config(config, config_dir, parent)?;
+/// Runs the checker, with the scan cache if a directory enables it, handing each result to `f`
+/// as soon as it's checked.
+///
+/// Files already misplaced in the baseline are left out of the results, and the files of the
+/// baseline that are no longer misplaced are returned.
+fn run_checker<'a>(
+    config: &Config,
+    config_dir: &Path,
+    checker: &'a Checker,
+    baseline: Option<&Baseline>,
+    mut f: impl FnMut(&'a DirectoryChecker, CheckerResult, Duration) -> io::Result<()>,
+) -> io::Result<Vec<PathBuf>> {
+    let mut cache = checker
+        .directories
+        .iter()
+        .any(|directory| directory.cache)
+        .then(|| ScanCache::load(config_dir));
+    let mut resolved = Vec::new();
+    let output = checker.run_with(cache.as_mut(), |directory, mut result, elapsed| {
+        if let (Some(baseline), CheckerResult::Ok(report)) = (baseline, &mut result) {
+            resolved.extend(baseline.subtract(&directory.path, report));
+        }
+        f(directory, result, elapsed)
+    });
+    match cache.as_mut().map(ScanCache::save) {
+        Some(Err(_)) if config.settings.quiet => {}
+        Some(Err(err)) if config.settings.color => {
+            eprintln!("{} {err}", "Heads up!".bright_red().bold());
+        }
+        Some(Err(err)) => eprintln!("Heads up! {err}"),
+        _ => {}
+    }
+    output.map(|()| resolved)
+}
+
+pub(crate) fn setup_checker(
+    config: &Config,
+    selection: &Selection,
+    targets: &[PathBuf],
+    allow: &[String],
+    sort: SortOrder,
+) -> anyhow::Result<Checker> {
+    if !allow.is_empty() {
+        return adhoc_checker(&config.settings, targets, allow);
+    }
+    // Unselected directories aren't set up, so their rules can't fail the check
+    let mut names = config
+        .directories
+        .iter()
+        .filter(|(_, dir_config)| dir_config.enabled)
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+    selection.validate(names)?;
+    let mut checker = Checker::from_config_filtered(config, None, |name| selection.includes(name))?;
+
+    if checker.directories.is_empty() {
+        if config.settings.color {
+            bail!(
+                "{} No directories were configured to be checked.",
+                "(!)".bold()
+            );
+        }
+        bail!("(!) No directories were configured to be checked.");
+    }
+    checker
+        .directories
+        .retain(|dir| is_in_targets(&dir.path, targets));
+
+    if sort == SortOrder::Name {
+        checker.directories.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+    let noatime_warning = super::noatime_warning(
+        config,
+        checker
+            .directories
+            .iter()
+            .filter(|directory| directory.access_times_unrecorded())
+            .map(|directory| directory.path.as_path()),
+    );
+    if noatime_warning {
+        eprintln!(); // Print newline after info message
+    }
+    Ok(checker)
+}
+
+/// Sets up a checker for directories that may not be configured, with inline allowed files
+fn adhoc_checker(
+    settings: &Settings,
+    targets: &[PathBuf],
+    allow: &[String],
+) -> anyhow::Result<Checker> {
+    let dir_config = DirectoryConfig {
+        allowed_files: Some(
+            allow
+                .iter()
+                .map(|glob| MatchRule::Glob { glob: glob.clone() })
+                .collect(),
+        ),
+        ..DirectoryConfig::default()
+    };
+    let directories = targets
+        .iter()
+        .map(|target| {
+            crate::checker::directory_from_config(&target.to_string_lossy(), &dir_config, settings)
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok(Checker {
+        parent: None,
+        directories,
+    })
+}
+
+/// Reads target directories, one per line
+fn read_targets(input: impl BufRead) -> anyhow::Result<Vec<PathBuf>> {
+    let mut targets = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let target = fs::canonicalize(&line)
+            .map_err(|err| anyhow::format_err!("Invalid target '{line}': {err}"))?;
+        targets.push(target);
+    }
+    Ok(targets)
+}
+
+/// Whether a path is under one of the targets, any path being when there are none
+fn is_in_targets(path: &Path, targets: &[PathBuf]) -> bool {
+    targets.is_empty() || targets.iter().any(|target| path.starts_with(target))
+}
+
+/// Prints how many files could be moved by auto-move, as set by `report-info`
+fn show_automove_info(
+    config: &Config,
+    config_dir: &Path,
+    targets: &[PathBuf],
+    footer_sep: bool,
+    all_hidden: bool,
+) -> anyhow::Result<()> {
+    let mut automove = crate::automove::AutoMove::from_config(config, config_dir, None)?;
+    automove
+        .rules
+        .retain(|rule| is_in_targets(&rule.directory, targets));
     match config.automove.report_info {
         AutoMoveReportInfo::Any if automove.would_move_any() => {
-            if !footer_sep && results_len != hidden {
+            if !footer_sep && !all_hidden {
                 println!();
             }
             if config.settings.color {
@@ -103,32 +499,219 @@ pub fn execute(
                 if config.settings.color {
                     println!(
                         "{} {} {}",
-                        format!("{}", count).bright_yellow().bold(),
+                        format!("{count}").bright_yellow().bold(),
                         "files can be automatically moved!".yellow().bold(),
                         "(Run auto-move command)".white().dimmed()
                     );
                 } else {
-                    println!(
-                        "{} files can be automatically moved! (Run auto-move command)",
-                        count
-                    );
+                    println!("{count} files can be automatically moved! (Run auto-move command)");
                 }
             }
         }
         _ => {}
-    };
+    }
 
     Ok(())
 }
 
+/// Misplaced files of a report saved with `report --format json`, which `--since` leaves out
+#[derive(Deserialize)]
+struct Baseline {
+    directories: Vec<BaselineDirectory>,
+}
+
+#[derive(Deserialize)]
+struct BaselineDirectory {
+    path: PathBuf,
+    issues: Vec<BaselineIssue>,
+}
+
+#[derive(Deserialize)]
+struct BaselineIssue {
+    path: PathBuf,
+}
+
+impl Baseline {
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|err| {
+            format_err!("Could not read baseline {}: {err}", path.to_string_lossy())
+        })?;
+        serde_json::from_str(&contents).map_err(|err| {
+            format_err!(
+                "Invalid baseline {}, expected the output of `report --format json`: {err}",
+                path.to_string_lossy()
+            )
+        })
+    }
+
+    /// Leaves out the files of a directory's report that were already misplaced in the
+    /// baseline, returning the files of the baseline that no longer are
+    fn subtract(&self, directory: &Path, report: &mut Report) -> Vec<PathBuf> {
+        let Some(known) = self
+            .directories
+            .iter()
+            .find(|known| known.path == directory)
+        else {
+            return Vec::new();
+        };
+        let known = known
+            .issues
+            .iter()
+            .map(|issue| issue.path.as_path())
+            .collect::<HashSet<_>>();
+        let current = report
+            .issues
+            .iter()
+            .map(ReportIssue::path)
+            .collect::<HashSet<_>>();
+        let mut resolved = known
+            .difference(&current)
+            .map(|path| path.to_path_buf())
+            .collect::<Vec<_>>();
+        resolved.sort();
+        report.issues.retain(|issue| !known.contains(issue.path()));
+        resolved
+    }
+}
+
+/// Lists the files of the baseline that are no longer misplaced
+fn print_resolved(settings: &Settings, resolved: &[PathBuf]) {
+    if resolved.is_empty() {
+        return;
+    }
+    let header = format!("Resolved since the baseline ({}):", resolved.len());
+    if settings.color {
+        println!("{}", header.green().bold());
+    } else {
+        println!("{header}");
+    }
+    for path in resolved {
+        println!("  {}", path.to_string_lossy());
+    }
+    println!();
+}
+
+/// Lists how long each directory took to check, slowest first, highlighting the slowest ones
+fn print_timings(settings: &Settings, timings: &[(PathBuf, Duration)]) {
+    if timings.is_empty() {
+        return;
+    }
+    let mut timings = timings.iter().collect::<Vec<_>>();
+    timings.sort_by_key(|(_, elapsed)| Reverse(*elapsed));
+    let total = timings
+        .iter()
+        .map(|(_, elapsed)| *elapsed)
+        .sum::<Duration>();
+    let header = format!("Timings ({total:.2?} in total):");
+    if settings.color {
+        println!("{}", header.bright_white().bold());
+    } else {
+        println!("{header}");
+    }
+    for (i, (path, elapsed)) in timings.into_iter().enumerate() {
+        let elapsed = format!("{elapsed:>10.2?}");
+        if settings.color && i < SLOWEST_HIGHLIGHTED {
+            println!(
+                "  {} {}",
+                elapsed.bright_red().bold(),
+                path.to_string_lossy()
+            );
+        } else if settings.color {
+            println!("  {} {}", elapsed.dimmed(), path.to_string_lossy());
+        } else {
+            println!("  {elapsed} {}", path.to_string_lossy());
+        }
+    }
+    println!();
+}
+
+/// Totals across all checked directories
+#[derive(Debug, Default)]
+struct Summary {
+    scanned: usize,
+    /// Misplaced entries of any kind
+    misplaced: usize,
+    misplaced_dirs: usize,
+    misplaced_files: usize,
+    symlinks: usize,
+    broken_symlinks: usize,
+    unreadable: usize,
+}
+
+impl Summary {
+    fn add(&mut self, report: &Report) {
+        self.scanned += 1;
+        self.misplaced += report.issues.len();
+        self.unreadable += report.errors.len();
+        for issue in &report.issues {
+            match issue.kind() {
+                IssueKind::Directory => self.misplaced_dirs += 1,
+                IssueKind::File => self.misplaced_files += 1,
+                IssueKind::Symlink => self.symlinks += 1,
+                IssueKind::BrokenSymlink => self.broken_symlinks += 1,
+            }
+        }
+    }
+
+    fn print(&self, settings: &Settings) {
+        let Summary {
+            scanned,
+            misplaced_dirs,
+            misplaced_files,
+            symlinks,
+            broken_symlinks,
+            unreadable,
+            ..
+        } = self;
+        let total = misplaced_dirs + misplaced_files + symlinks + broken_symlinks;
+        let symlinks = match symlinks {
+            0 => String::new(),
+            symlinks => format!(", {symlinks} symlinks"),
+        };
+        let broken_symlinks = match broken_symlinks {
+            0 => String::new(),
+            broken_symlinks => format!(", {broken_symlinks} broken symlinks"),
+        };
+        let icon = if settings.unicode && !settings.glyphs.search.is_empty() {
+            format!("{} ", settings.glyphs.search)
+        } else {
+            String::new()
+        };
+        let unreadable = match unreadable {
+            0 => String::new(),
+            unreadable => format!(", could not read {unreadable} entries"),
+        };
+        if settings.color {
+            println!(
+                "{}{} {} {}{}",
+                icon.bright_white(),
+                format!("Scanned {scanned} directories,")
+                    .bright_white()
+                    .bold(),
+                format!("{total} misplaced files").bright_yellow().bold(),
+                format!(
+                    "({misplaced_dirs} dirs, {misplaced_files} files{symlinks}{broken_symlinks})"
+                )
+                .white()
+                .dimmed(),
+                unreadable.red().bold(),
+            );
+        } else {
+            println!(
+                "{icon}Scanned {scanned} directories, {total} misplaced files ({misplaced_dirs} dirs, {misplaced_files} files{symlinks}{broken_symlinks}){unreadable}"
+            );
+        }
+    }
+}
+
 fn show_hidden_info(config: &Config, hidden: usize) {
     if config.settings.color {
         println!(
             "{} {}",
             if config.settings.unicode {
-                format!("\u{f00c} {} directories", hidden)
+                format!("{} {hidden} directories", config.settings.glyphs.checkmark)
             } else {
-                format!("{} directories", hidden)
+                format!("{hidden} directories")
             }
             .bright_white()
             .bold()
@@ -141,17 +724,43 @@ fn show_hidden_info(config: &Config, hidden: usize) {
         println!(
             "{} directories were hidden from the output (no misplaced children)",
             if config.settings.unicode {
-                format!("\u{f00c} {}", hidden)
+                format!("{} {hidden}", config.settings.glyphs.checkmark)
             } else {
-                format!("{}", hidden)
+                format!("{hidden}")
             },
         );
     }
 }
 
-fn print_report(settings: &Settings, report: &Report) {
+fn print_error(settings: &Settings, result: &CheckerResult) {
+    if settings.color {
+        eprintln!(
+            "{} {}",
+            result.path().to_string_lossy().red(),
+            result.format_err()
+        );
+    } else {
+        eprintln!(
+            "{}: {}",
+            result.path().to_string_lossy(),
+            result.format_err()
+        );
+    }
+}
+
+fn print_report(
+    settings: &Settings,
+    report: &Report,
+    level: OutputLevel,
+    sizes: bool,
+    show_mtime: bool,
+) {
     if report.issues.is_empty() {
-        let checkmark = if settings.unicode { "\u{f00c}" } else { "OK" };
+        let checkmark = if settings.unicode {
+            &settings.glyphs.checkmark
+        } else {
+            "OK"
+        };
         if settings.color {
             println!(
                 "{} {}",
@@ -164,9 +773,13 @@ fn print_report(settings: &Settings, report: &Report) {
         return;
     }
 
-    let xmark = if settings.unicode { "\u{f467}" } else { "X" };
+    let xmark = if settings.unicode {
+        &settings.glyphs.xmark
+    } else {
+        "X"
+    };
     let total_files = report.issues.len();
-    let misplaced_files_str = format!("{} misplaced files", total_files);
+    let misplaced_files_str = format!("{total_files} misplaced files");
     if settings.color {
         println!(
             "{} {} {}",
@@ -182,61 +795,207 @@ fn print_report(settings: &Settings, report: &Report) {
             misplaced_files_str
         );
     }
+    if level == OutputLevel::Summary {
+        return;
+    }
 
-    let (directories_str, directories_count) =
-        joined_rel_files(settings, report, |issue| issue.file_metadata().is_dir());
-    let (files_str, files_count) =
-        joined_rel_files(settings, report, |issue| issue.file_metadata().is_file());
-    if settings.color {
-        if directories_count > 0 {
+    for kind in IssueKind::ALL {
+        let label = kind.label();
+        let (files_str, files_count) =
+            joined_rel_files(settings, report, sizes, show_mtime, |issue| {
+                issue.kind() == kind
+            });
+        if files_count == 0 {
+            continue;
+        }
+        if settings.color {
             println!(
                 "{} {}{} {}",
-                "Directories".bright_white().bold(),
-                format!("({})", directories_count).bright_yellow().bold(),
+                label.bright_white().bold(),
+                format!("({files_count})").bright_yellow().bold(),
                 ":".bright_white().bold(),
-                directories_str
+                files_str
             );
+        } else {
+            println!("{label} ({files_count}): {files_str}");
         }
-        if files_count > 0 {
+    }
+}
+
+/// Prints why each misplaced file of a report is misplaced, one per line
+fn print_explanations(settings: &Settings, directory: &DirectoryChecker, report: &Report) {
+    let mut issues = report.issues.iter().collect::<Vec<_>>();
+    issues.sort_by_key(|issue| issue.path());
+    for issue in issues {
+        let rel_path = issue
+            .path()
+            .strip_prefix(&report.path)
+            .unwrap_or(issue.path());
+        let explanation = directory.explain(issue);
+        if settings.color {
             println!(
-                "{} {}{} {}",
-                "Files".bright_white().bold(),
-                format!("({})", files_count).bright_yellow().bold(),
-                ":".bright_white().bold(),
-                files_str
+                "  {}: {}",
+                rel_path.to_string_lossy().white(),
+                explanation.dimmed()
             );
+        } else {
+            println!("  {}: {explanation}", rel_path.to_string_lossy());
         }
+    }
+}
+
+/// Warns that some entries of a report couldn't be read, listing them with `explain`
+fn print_read_errors(settings: &Settings, report: &Report, explain: bool) {
+    if report.errors.is_empty() {
+        return;
+    }
+    let note = format!(
+        "Could not read {} entries, they were not checked",
+        report.errors.len()
+    );
+    if settings.color {
+        println!("{}", note.red());
     } else {
-        if directories_count > 0 {
-            println!("Directories ({}): {}", directories_count, directories_str);
+        println!("{note}");
+    }
+    if !explain {
+        return;
+    }
+    for (path, err) in &report.errors {
+        let rel_path = path.strip_prefix(&report.path).unwrap_or(path);
+        if settings.color {
+            println!(
+                "  {}: {}",
+                rel_path.to_string_lossy().white(),
+                err.to_string().dimmed()
+            );
+        } else {
+            println!("  {}: {err}", rel_path.to_string_lossy());
+        }
+    }
+}
+
+/// Prints the misplaced files of a report without formatting, one per line or NUL terminated.
+///
+/// Entries that couldn't be read are listed on stderr.
+fn print_list(report: &Report, print0: bool, show_mtime: bool) -> io::Result<()> {
+    for (path, err) in &report.errors {
+        eprintln!("Could not read {}: {err}", path.to_string_lossy());
+    }
+    let mut stdout = io::stdout().lock();
+    for issue in &report.issues {
+        if print0 {
+            super::print_path0(&mut stdout, issue.path())?;
+            continue;
         }
-        if files_count > 0 {
-            println!("Files ({}): {}", files_count, files_str);
+        let path = issue.path().to_string_lossy();
+        match modified(issue) {
+            Some(time) if show_mtime => writeln!(
+                stdout,
+                "{path}\t{}",
+                time.to_rfc3339_opts(SecondsFormat::Secs, false)
+            )?,
+            _ => writeln!(stdout, "{path}")?,
         }
     }
+    Ok(())
+}
+
+/// Returns when a misplaced file was last modified, in local time
+fn modified(issue: &ReportIssue) -> Option<DateTime<Local>> {
+    issue.file_metadata().modified().ok().map(DateTime::from)
 }
 
-fn joined_rel_files<P>(settings: &Settings, report: &Report, predicate: P) -> (String, usize)
+fn joined_rel_files<P>(
+    settings: &Settings,
+    report: &Report,
+    sizes: bool,
+    show_mtime: bool,
+    predicate: P,
+) -> (String, usize)
 where
     P: FnMut(&&ReportIssue) -> bool,
 {
-    let it = report
-        .issues
-        .iter()
-        .filter(predicate)
-        .filter_map(|issue| issue.path().strip_prefix(&report.path).ok())
-        .map(std::path::Path::to_string_lossy);
+    let it = report.issues.iter().filter(predicate).filter_map(|issue| {
+        let rel_path = issue.path().strip_prefix(&report.path).ok()?;
+        Some((issue, rel_path.to_string_lossy()))
+    });
+    let details_of = |issue: &ReportIssue| {
+        let mut details = Vec::new();
+        if sizes && issue.file_metadata().is_dir() {
+            let entries = fs::read_dir(issue.path()).map_or(0, Iterator::count);
+            details.push(format!("{entries} entries"));
+        } else if sizes {
+            details.push(super::human_size(issue.file_metadata().len()));
+        }
+        if let Some(time) = modified(issue).filter(|_| show_mtime) {
+            details.push(format!("modified {}", time.format("%Y-%m-%d %H:%M")));
+        }
+        (!details.is_empty()).then(|| format!("({})", details.join(", ")))
+    };
+    let mut tmp = it
+        .map(|(issue, path)| match (details_of(issue), settings.color) {
+            (None, false) => path.to_string(),
+            (None, true) => format!("{}", path.white()),
+            (Some(details), false) => format!("{path} {details}"),
+            (Some(details), true) => format!("{} {}", path.white(), details.dimmed()),
+        })
+        .collect::<Vec<_>>();
+    let count = tmp.len();
+    tmp.sort();
+    if settings.max_display > 0 && count > settings.max_display {
+        tmp.truncate(settings.max_display);
+        let more = format!("... and {} more", count - settings.max_display);
+        if settings.color {
+            tmp.push(format!("{}", more.dimmed()));
+        } else {
+            tmp.push(more);
+        }
+    }
     if settings.color {
-        let mut tmp = it
-            .map(|path| format!("{}", path.white()))
-            .collect::<Vec<_>>();
-        let count = tmp.len();
-        tmp.sort();
         (tmp.join(&format!("{} ", ",".bright_black())), count)
     } else {
-        let mut tmp = it.collect::<Vec<_>>();
-        let count = tmp.len();
-        tmp.sort();
         (tmp.join(", "), count)
     }
 }
+
+#[test]
+fn test_baseline_subtract() {
+    let root = super::test_fixture("baseline", &["old.txt", "new.txt", "notes.md"]);
+    let config: Config = toml::from_str(&format!(
+        "[settings]\n[automove]\n[dir.{:?}]\nallowed-files = [{{ ext = \"md\" }}]\n",
+        root.to_string_lossy()
+    ))
+    .unwrap();
+    let checker = Checker::from_config(&config, None).unwrap();
+    let CheckerResult::Ok(mut report) = checker.directories[0].check(None) else {
+        panic!("fixture directory could not be checked");
+    };
+    let baseline: Baseline = serde_json::from_value(serde_json::json!({
+        "directories": [
+            {
+                "path": root,
+                "issues": [{ "path": root.join("old.txt") }, { "path": root.join("fixed.txt") }],
+            },
+            { "path": root.join("other"), "issues": [{ "path": root.join("new.txt") }] },
+        ],
+    }))
+    .unwrap();
+
+    let resolved = baseline.subtract(&root, &mut report);
+    assert_eq!(vec![root.join("fixed.txt")], resolved);
+    assert_eq!(
+        vec![root.join("new.txt").as_path()],
+        report
+            .issues
+            .iter()
+            .map(ReportIssue::path)
+            .collect::<Vec<_>>()
+    );
+    // directories the baseline doesn't know about are left untouched
+    assert!(baseline
+        .subtract(&root.join("unknown"), &mut report)
+        .is_empty());
+    assert_eq!(1, report.issues.len());
+    fs::remove_dir_all(root).unwrap();
+}
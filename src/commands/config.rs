@@ -0,0 +1,42 @@
+use std::path::Path;
+
+use colored::Colorize;
+
+use crate::automove::AutoMove;
+use crate::checker::Checker;
+use crate::cli::ConfigCommand;
+use crate::config::Config;
+
+pub fn execute(config: &Config, config_dir: &Path, command: &ConfigCommand) -> anyhow::Result<()> {
+    match command {
+        ConfigCommand::Dump => dump(config, config_dir),
+    }
+}
+
+/// Prints every enabled directory and auto-move rule as they are run, e.g. to see what
+/// environment variables expanded to and which regexes filename rules compiled to
+fn dump(config: &Config, config_dir: &Path) -> anyhow::Result<()> {
+    let checker = Checker::from_config(config, None)?;
+    let automove = AutoMove::from_config(config, config_dir, None)?;
+
+    for directory in &checker.directories {
+        print_header(config, &format!("[dir.\"{}\"]", directory.name));
+        println!("{directory:#?}\n");
+    }
+    for rule in &automove.rules {
+        print_header(
+            config,
+            &format!("[[automove.rules]] {}", rule.display_name()),
+        );
+        println!("{rule:#?}\n");
+    }
+    Ok(())
+}
+
+fn print_header(config: &Config, header: &str) {
+    if config.settings.color {
+        println!("{}", header.bright_white().bold());
+    } else {
+        println!("{header}");
+    }
+}
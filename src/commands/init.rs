@@ -0,0 +1,132 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+
+use anyhow::bail;
+
+use crate::cli::{InitArgs, Preset};
+
+/// Directories of the bundled config that can be picked, with a short description
+const SUGGESTED_DIRECTORIES: [(&str, &str); 4] = [
+    ("$HOME", "home directory"),
+    ("$XDG_DOWNLOAD_DIR", "downloads"),
+    ("$XDG_MUSIC_DIR", "music"),
+    ("$XDG_VIDEOS_DIR", "videos"),
+];
+
+const HOME_PRESET: &str = include_str!("../../presets/home.toml");
+const HOME_DIRECTORIES: [(&str, &str); 6] = [
+    ("$HOME", "home directory"),
+    ("$XDG_DESKTOP_DIR", "desktop"),
+    ("$XDG_DOCUMENTS_DIR", "documents"),
+    ("$XDG_PICTURES_DIR", "pictures"),
+    ("$XDG_MUSIC_DIR", "music"),
+    ("$XDG_VIDEOS_DIR", "videos"),
+];
+
+const PHOTOGRAPHER_PRESET: &str = include_str!("../../presets/photographer.toml");
+const PHOTOGRAPHER_DIRECTORIES: [(&str, &str); 3] = [
+    ("$XDG_PICTURES_DIR", "pictures"),
+    ("$XDG_DOWNLOAD_DIR", "downloads"),
+    ("$XDG_DESKTOP_DIR", "desktop"),
+];
+
+pub fn execute(config_path: &Path, template: &str, args: &InitArgs) -> anyhow::Result<()> {
+    if !args.force && config_path.try_exists().unwrap_or(true) {
+        bail!(
+            "(!) A config file already exists at {}. Use --force to overwrite it.",
+            config_path.to_string_lossy()
+        );
+    }
+
+    let (template, suggested_directories) = match args.preset {
+        Preset::Default => (template, &SUGGESTED_DIRECTORIES[..]),
+        Preset::Home => (HOME_PRESET, &HOME_DIRECTORIES[..]),
+        Preset::Photographer => (PHOTOGRAPHER_PRESET, &PHOTOGRAPHER_DIRECTORIES[..]),
+    };
+
+    let interactive = !args.yes && io::stdin().is_terminal();
+    let mut selected = Vec::new();
+    for &(dir, description) in suggested_directories {
+        // directories unknown on this system would only make the config invalid
+        let Ok(resolved) = env::var(dir.trim_start_matches('$')) else {
+            continue;
+        };
+        if !interactive || ask(&format!("Check your {description} ({resolved})?"))? {
+            selected.push(dir);
+        }
+    }
+
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(config_path, tailor_config(template, &selected))?;
+    println!(
+        "Config written to {} with {} directories. Auto-move is in dry run until you turn off 'force-dry-run'.",
+        config_path.to_string_lossy(),
+        selected.len()
+    );
+    Ok(())
+}
+
+/// Asks a yes/no question, defaulting to yes
+fn ask(question: &str) -> anyhow::Result<bool> {
+    print!("{question} [Y/n] ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    Ok(answer.is_empty() || answer == "y" || answer == "yes")
+}
+
+/// Removes the `[dir."..."]` sections of the template that were not selected.
+///
+/// Sections are separated from the rest of the template by blank lines.
+fn tailor_config(template: &str, selected: &[&str]) -> String {
+    template
+        .split("\n\n")
+        .filter(|section| {
+            let Some(dir) = section
+                .strip_prefix("[dir.\"")
+                .and_then(|rest| rest.split_once("\"]"))
+                .map(|(dir, _)| dir)
+            else {
+                return true;
+            };
+            selected.contains(&dir)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[test]
+fn test_tailor_config() {
+    let template = include_str!("../../shinydir.toml");
+    for (dir, _) in SUGGESTED_DIRECTORIES {
+        assert!(template.contains(&format!("\n\n[dir.\"{dir}\"]")));
+    }
+    let config = tailor_config(template, &["$XDG_MUSIC_DIR"]);
+    assert!(config.contains("[dir.\"$XDG_MUSIC_DIR\"]"));
+    assert!(!config.contains("[dir.\"$HOME\"]"));
+    assert!(!config.contains("[dir.\"$XDG_VIDEOS_DIR\"]"));
+    assert!(config.contains("[automove]"));
+    toml::from_str::<crate::config::Config>(&config).unwrap();
+}
+
+#[test]
+fn test_presets() {
+    let presets = [
+        (HOME_PRESET, &HOME_DIRECTORIES[..]),
+        (PHOTOGRAPHER_PRESET, &PHOTOGRAPHER_DIRECTORIES[..]),
+    ];
+    for (template, directories) in presets {
+        let dirs = directories.iter().map(|(dir, _)| *dir).collect::<Vec<_>>();
+        let config: crate::config::Config =
+            toml::from_str(&tailor_config(template, &dirs)).unwrap();
+        assert_eq!(config.directories.len(), dirs.len());
+        let config: crate::config::Config = toml::from_str(&tailor_config(template, &[])).unwrap();
+        assert!(config.directories.is_empty());
+        assert!(config.automove.force_dry_run);
+    }
+}
@@ -1,2 +1,99 @@
 pub(crate) mod automove;
 pub(crate) mod check;
+pub(crate) mod config;
+pub(crate) mod init;
+pub(crate) mod report;
+pub(crate) mod tidy;
+pub(crate) mod undo;
+pub(crate) mod validate;
+
+use std::path::Path;
+
+use colored::Colorize;
+
+use crate::config::Config;
+
+/// Warns about directories with `accessed-older-than` rules on filesystems that don't record
+/// access times, where files look unread since they were created.
+///
+/// Returns whether a warning was printed.
+pub(crate) fn noatime_warning<'a>(
+    config: &Config,
+    directories: impl IntoIterator<Item = &'a Path>,
+) -> bool {
+    let mut directories = directories
+        .into_iter()
+        .map(|path| path.to_string_lossy())
+        .collect::<Vec<_>>();
+    directories.dedup();
+    if directories.is_empty() || config.settings.quiet {
+        return false;
+    }
+    // print on stderr to not affect pipe input (e.g. when using --list)
+    if config.settings.color {
+        eprintln!(
+            "{} These directories are on filesystems that don't record when files are read (noatime), {} rules can't tell read files apart there: {}",
+            "Heads up!".bright_red().bold(),
+            "accessed-older-than".dimmed(),
+            directories.join(", ").bold()
+        );
+    } else {
+        eprintln!(
+            "Heads up! These directories are on filesystems that don't record when files are read (noatime), accessed-older-than rules can't tell read files apart there: {}",
+            directories.join(", ")
+        );
+    }
+    true
+}
+
+/// Writes a path followed by a NUL character, keeping non UTF-8 paths intact where possible
+pub(crate) fn print_path0(
+    out: &mut impl std::io::Write,
+    path: &std::path::Path,
+) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        out.write_all(path.as_os_str().as_bytes())?;
+    }
+    #[cfg(not(unix))]
+    out.write_all(path.to_string_lossy().as_bytes())?;
+    out.write_all(b"\0")
+}
+
+/// Formats a size in bytes with binary units, e.g. `1.5 MiB`
+pub(crate) fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Same as the library's `checker::test_fixture`, whose test helpers aren't built for the binary
+#[cfg(test)]
+pub(crate) fn test_fixture(name: &str, files: &[&str]) -> std::path::PathBuf {
+    let root = std::env::temp_dir().join(format!("shinydir-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    for file in files {
+        let path = root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    root
+}
+
+#[test]
+fn test_human_size() {
+    assert_eq!("512 B", human_size(512));
+    assert_eq!("1.0 KiB", human_size(1024));
+    assert_eq!("1.5 MiB", human_size(1024 * 1024 * 3 / 2));
+    assert_eq!("2.0 GiB", human_size(2 * 1024 * 1024 * 1024));
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::automove::{AutoMove, AutoMoveResult};
+use crate::checker::{Checker, CheckerResult, DirectoryChecker, IssueReason};
+use crate::cli::{OutputFormat, ReportArgs};
+use crate::config::Config;
+
+/// Combined report of misplaced files and planned moves
+#[derive(Serialize)]
+struct JsonReport {
+    directories: Vec<JsonDirectory>,
+    moves: Vec<JsonMove>,
+}
+
+/// A checked directory as printed in the JSON report
+#[derive(Serialize)]
+struct JsonDirectory {
+    name: String,
+    path: PathBuf,
+    /// One of "ok", "missing" or "not-a-directory"
+    status: &'static str,
+    issues: Vec<JsonIssue>,
+    /// Entries that couldn't be read, and were therefore not checked
+    errors: Vec<JsonReadError>,
+}
+
+/// An unreadable entry as printed in the JSON report
+#[derive(Serialize)]
+struct JsonReadError {
+    path: PathBuf,
+    error: String,
+}
+
+/// A misplaced file as printed in the JSON report
+#[derive(Serialize)]
+struct JsonIssue {
+    path: PathBuf,
+    /// One of "file", "directory" or "other"
+    kind: &'static str,
+    reason: IssueReason,
+    explanation: String,
+    /// The planned move that would resolve this issue, if any
+    #[serde(rename = "move")]
+    planned_move: Option<JsonPlannedMove>,
+}
+
+#[derive(Clone, Serialize)]
+struct JsonPlannedMove {
+    rule: String,
+    destination: PathBuf,
+}
+
+/// A move as printed in the JSON report
+#[derive(Serialize)]
+struct JsonMove {
+    rule: String,
+    source: Option<PathBuf>,
+    destination: Option<PathBuf>,
+    /// One of "planned" or "error"
+    status: &'static str,
+    error: Option<String>,
+    /// Whether the source is reported as misplaced by a checked directory
+    resolves_issue: bool,
+}
+
+pub fn execute(config: &Config, config_dir: &Path, args: &ReportArgs) -> anyhow::Result<()> {
+    let parent = args.target.clone().map(fs::canonicalize).transpose()?;
+    let checker = Checker::from_config(config, parent.clone())?;
+    let automove = AutoMove::from_config(config, config_dir, parent)?;
+
+    let mut moves = Vec::new();
+    for result in automove.run() {
+        let AutoMoveResult::Ok {
+            rule,
+            entries,
+            errors,
+            ..
+        } = result
+        else {
+            continue;
+        };
+        for (path, err) in errors {
+            moves.push(JsonMove {
+                rule: rule.display_name(),
+                source: None,
+                destination: None,
+                status: "error",
+                error: Some(format!("Could not read {}: {err}", path.to_string_lossy())),
+                resolves_issue: false,
+            });
+        }
+        for entry in entries {
+            moves.push(match entry {
+                Ok(entry) => JsonMove {
+                    rule: rule.display_name(),
+                    source: Some(entry.file),
+                    destination: Some(entry.move_to),
+                    status: "planned",
+                    error: None,
+                    resolves_issue: false,
+                },
+                Err(err) => JsonMove {
+                    rule: rule.display_name(),
+                    source: None,
+                    destination: None,
+                    status: "error",
+                    error: Some(err.to_string()),
+                    resolves_issue: false,
+                },
+            });
+        }
+    }
+    let planned_moves = moves
+        .iter()
+        .filter_map(|planned| {
+            let planned_move = JsonPlannedMove {
+                rule: planned.rule.clone(),
+                destination: planned.destination.clone()?,
+            };
+            Some((planned.source.clone()?, planned_move))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let directories = checker
+        .run(None)
+        .into_iter()
+        .map(|(directory, result)| json_directory(directory, result, &planned_moves))
+        .collect::<Vec<_>>();
+    for planned in &mut moves {
+        planned.resolves_issue = planned.source.as_ref().is_some_and(|source| {
+            directories
+                .iter()
+                .any(|directory| directory.issues.iter().any(|issue| &issue.path == source))
+        });
+    }
+
+    let report = JsonReport { directories, moves };
+    match args.format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Text => print_text(config, &report),
+    }
+    Ok(())
+}
+
+fn json_directory(
+    directory: &DirectoryChecker,
+    result: CheckerResult,
+    planned_moves: &HashMap<PathBuf, JsonPlannedMove>,
+) -> JsonDirectory {
+    let (status, issues, errors) = match result {
+        CheckerResult::Ok(report) => ("ok", report.issues, report.errors),
+        CheckerResult::MissingDirectory { .. } => ("missing", Vec::new(), Vec::new()),
+        CheckerResult::NotADirectory { .. } => ("not-a-directory", Vec::new(), Vec::new()),
+    };
+    let errors = errors
+        .into_iter()
+        .map(|(path, err)| JsonReadError {
+            path,
+            error: err.to_string(),
+        })
+        .collect();
+    let issues = issues
+        .iter()
+        .map(|issue| JsonIssue {
+            path: issue.path().to_path_buf(),
+            kind: if issue.file_metadata().is_dir() {
+                "directory"
+            } else if issue.file_metadata().is_file() {
+                "file"
+            } else {
+                "other"
+            },
+            reason: issue.reason(),
+            explanation: directory.explain(issue),
+            planned_move: planned_moves.get(issue.path()).cloned(),
+        })
+        .collect();
+    JsonDirectory {
+        name: directory.name.clone(),
+        path: directory.path.clone(),
+        status,
+        issues,
+        errors,
+    }
+}
+
+/// Prints every misplaced file along with where auto-move would put it
+fn print_text(config: &Config, report: &JsonReport) {
+    let mut first_entry = true;
+    for directory in &report.directories {
+        if directory.issues.is_empty() && directory.errors.is_empty() && directory.status == "ok" {
+            continue;
+        }
+        if first_entry {
+            first_entry = false;
+        } else {
+            println!();
+        }
+        let path = directory.path.to_string_lossy();
+        match directory.status {
+            "ok" if config.settings.color => println!("{}", path.yellow()),
+            "ok" => println!("{path}"),
+            status if config.settings.color => println!("{} {status}", path.red()),
+            status => println!("{path}: {status}"),
+        }
+        for issue in &directory.issues {
+            let file = issue.path.to_string_lossy();
+            match &issue.planned_move {
+                Some(planned) => println!(
+                    "  {file} -> {} ({})",
+                    planned.destination.to_string_lossy(),
+                    planned.rule
+                ),
+                None if config.settings.color => {
+                    println!("  {file} {}", "(no auto-move rule)".dimmed());
+                }
+                None => println!("  {file} (no auto-move rule)"),
+            }
+        }
+        for error in &directory.errors {
+            let file = error.path.to_string_lossy();
+            if config.settings.color {
+                println!(
+                    "  {file} {}",
+                    format!("(could not read: {})", error.error).red()
+                );
+            } else {
+                println!("  {file} (could not read: {})", error.error);
+            }
+        }
+    }
+    let unrelated = report
+        .moves
+        .iter()
+        .filter(|planned| !planned.resolves_issue)
+        .count();
+    if unrelated > 0 {
+        if !first_entry {
+            println!();
+        }
+        println!("{unrelated} other planned moves or errors are not about misplaced files (see --format json)");
+    }
+}
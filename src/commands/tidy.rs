@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+
+use crate::automove::AutoMoveResult;
+use crate::cli::{CheckArgs, Selection, SortOrder, TidyArgs};
+use crate::commands::automove::{self, Confirm, Layout};
+use crate::commands::check;
+use crate::config::Config;
+
+pub fn execute(config: &Config, config_dir: &Path, args: &TidyArgs) -> anyhow::Result<()> {
+    let parent = args.target.clone().map(fs::canonicalize).transpose()?;
+    let targets = parent.iter().cloned().collect::<Vec<_>>();
+
+    // Check
+    let check_args = CheckArgs {
+        target: parent.clone(),
+        ..CheckArgs::default()
+    };
+    let checker = check::setup_checker(
+        config,
+        &Selection::default(),
+        &targets,
+        &[],
+        SortOrder::Path,
+    )?;
+    let misplaced = check::report_misplaced(config, config_dir, &checker, &check_args)?
+        .iter()
+        .map(|path| canonical_parent(path))
+        .collect::<HashSet<_>>();
+    if misplaced.is_empty() {
+        return Ok(());
+    }
+    println!();
+
+    // Only move the files flagged by the check
+    let automove =
+        automove::setup_automove(config, config_dir, parent, &Selection::default(), false)?;
+    let mut dry_run = args.dry_run;
+    if automove::dry_run_warning(config, &mut dry_run, false) {
+        eprintln!();
+    }
+    let mut results = automove.run();
+    for result in &mut results {
+        if let AutoMoveResult::Ok { entries, .. } = result {
+            entries.retain(|entry| {
+                entry.as_ref().map_or(true, |entry| {
+                    misplaced.contains(&canonical_parent(&entry.file))
+                })
+            });
+        }
+    }
+    let movable = results
+        .iter()
+        .map(|result| match result {
+            AutoMoveResult::Ok { entries, .. } => entries.iter().filter(|e| e.is_ok()).count(),
+            AutoMoveResult::DirDoesNotExist { .. } => 0,
+        })
+        .sum::<usize>();
+    if movable == 0 {
+        println!("None of the misplaced files can be moved by auto-move rules.");
+        return Ok(());
+    }
+    if !dry_run && !args.yes && !confirm_moves(config, movable)? {
+        return Ok(());
+    }
+
+    // Move
+    automove::move_files(
+        config,
+        config_dir,
+        dry_run,
+        &mut Confirm::All,
+        None,
+        false,
+        &mut results,
+    )?;
+    if config.automove.notify && !dry_run {
+        automove::send_notification(&results);
+    }
+    automove::print_results(config, &results, dry_run, Layout::Compact);
+    Ok(())
+}
+
+/// Asks whether to move the files, only when the output is a terminal
+fn confirm_moves(config: &Config, movable: usize) -> anyhow::Result<bool> {
+    if !io::stdout().is_terminal() {
+        println!("{movable} files can be moved, run again with --yes to move them.");
+        return Ok(false);
+    }
+    let question = format!("Move {movable} files? [y/N]");
+    if config.settings.color {
+        eprint!("{} ", question.bold());
+    } else {
+        eprint!("{question} ");
+    }
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Returns a path with its parent directory canonicalized, so that the paths found by
+/// the check and by auto-move rules agree even if their directories are configured differently
+/// (e.g. through a symlink). The file itself isn't resolved, as it may be a symlink.
+fn canonical_parent(path: &Path) -> PathBuf {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(filename)) => fs::canonicalize(parent)
+            .map_or_else(|_| path.to_path_buf(), |parent| parent.join(filename)),
+        _ => path.to_path_buf(),
+    }
+}
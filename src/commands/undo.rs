@@ -0,0 +1,126 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::bail;
+use colored::Colorize;
+
+use crate::automove;
+use crate::config::Config;
+use crate::journal::{self, JournalEntry};
+
+pub fn execute(config: &Config, config_dir: &Path, previous: bool) -> anyhow::Result<()> {
+    if !previous && journal::is_last_run_undone(config_dir)? {
+        if config.settings.color {
+            bail!(
+                "{} The last auto-move run was already undone. Use {} to undo the run before it.",
+                "(!)".bold(),
+                "--previous".bold()
+            );
+        }
+        bail!("(!) The last auto-move run was already undone. Use --previous to undo the run before it.");
+    }
+    let Some(journal_path) = journal::latest(config_dir)? else {
+        if config.settings.color {
+            bail!("{} There is no auto-move run to undo.", "(!)".bold());
+        }
+        bail!("(!) There is no auto-move run to undo.");
+    };
+    let entries = journal::read(&journal_path)?;
+
+    let mut restored = 0;
+    let mut skipped = 0;
+    let mut remaining = Vec::new();
+    // undo in reverse order in case a file was moved several times
+    for entry in entries.into_iter().rev() {
+        // not following symlinks, as moved broken symlinks are restored too
+        if fs::symlink_metadata(&entry.to).is_err() {
+            skipped += 1;
+            let msg = format!("Skipped {} (no longer exists)", entry.to.to_string_lossy());
+            if config.settings.color {
+                eprintln!("{}", msg.dimmed());
+            } else {
+                eprintln!("{msg}");
+            }
+            continue;
+        }
+        match restore(&entry) {
+            Ok(()) => restored += 1,
+            Err(err) => {
+                eprintln!("{}", format!("{err}").bright_red().italic());
+                remaining.push(entry);
+            }
+        }
+    }
+    remaining.reverse();
+    if remaining.is_empty() {
+        journal::mark_undone(&journal_path)?;
+    } else {
+        journal::rewrite(&journal_path, &remaining)?;
+    }
+
+    let mut info = vec![format!("{restored} files restored")];
+    if skipped > 0 {
+        info.push(format!("{skipped} skipped"));
+    }
+    if !remaining.is_empty() {
+        info.push(format!("{} errors", remaining.len()));
+    }
+    if config.settings.color {
+        println!("{}", info.join(", ").bright_yellow());
+    } else {
+        println!("{}", info.join(", "));
+    }
+    if !remaining.is_empty() {
+        println!("Failed entries were kept in the journal, run undo again to retry them.");
+    }
+    Ok(())
+}
+
+fn restore(entry: &JournalEntry) -> anyhow::Result<()> {
+    if fs::symlink_metadata(&entry.from).is_ok() {
+        bail!(
+            "Restoring {} would overwrite a file",
+            entry.from.to_string_lossy()
+        );
+    }
+    if let Some(parent) = entry.from.parent() {
+        if let Err(err) = fs::create_dir_all(parent) {
+            bail!(
+                "Couldn't create directory {}: {}",
+                parent.to_string_lossy(),
+                err
+            );
+        }
+    }
+    if let Err(err) = automove::move_file(&entry.to, &entry.from, false) {
+        bail!(
+            "Couldn't move {} back to {}: {}",
+            entry.to.to_string_lossy(),
+            entry.from.to_string_lossy(),
+            err
+        );
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_undo_broken_symlink() {
+    let config_dir =
+        std::env::temp_dir().join(format!("shinydir-undo-broken-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&config_dir);
+    fs::create_dir_all(config_dir.join("dead")).unwrap();
+    let (from, to) = (config_dir.join("link"), config_dir.join("dead/link"));
+    std::os::unix::fs::symlink("missing", &to).unwrap();
+    let mut journal = journal::Journal::new(&config_dir);
+    journal.record(&from, &to).unwrap();
+
+    let config: Config = toml::from_str("[settings]\n[dir]\n[automove]\n").unwrap();
+    execute(&config, &config_dir, false).unwrap();
+    assert_eq!(
+        std::path::PathBuf::from("missing"),
+        fs::read_link(&from).unwrap()
+    );
+    assert!(fs::symlink_metadata(&to).is_err());
+    fs::remove_dir_all(config_dir).unwrap();
+}
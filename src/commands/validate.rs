@@ -0,0 +1,74 @@
+use std::path::Path;
+
+use anyhow::bail;
+use colored::Colorize;
+
+use crate::automove;
+use crate::checker;
+use crate::config::Config;
+
+pub fn execute(config: &Config, config_dir: &Path) -> anyhow::Result<()> {
+    let mut problems = Vec::new();
+
+    let mut dir_names = config.directories.keys().collect::<Vec<_>>();
+    dir_names.sort();
+    for dir_name in dir_names {
+        let dir_config = &config.directories[dir_name];
+        if let Err(err) = checker::directory_from_config(dir_name, dir_config, &config.settings) {
+            problems.push((format!("[dir.\"{dir_name}\"]"), err.to_string()));
+        }
+    }
+
+    for (i, config_rule) in config.automove.rules.iter().enumerate() {
+        let context = match &config_rule.name {
+            Some(name) => format!("[[automove.rules]] #{} ({name})", i + 1),
+            None => format!("[[automove.rules]] #{} ({})", i + 1, config_rule.parent),
+        };
+        let rule = match automove::rule_from_config(config, config_dir, config_rule) {
+            Ok(rule) => rule,
+            Err(err) => {
+                problems.push((context, err.to_string()));
+                continue;
+            }
+        };
+        if let Some(to_script) = &rule.to_script {
+            if let Err(err) = automove::check_script(to_script) {
+                problems.push((context.clone(), err.to_string()));
+            }
+        }
+        match automove::expand_parent_glob(rule) {
+            Ok(rules) => {
+                for rule in rules {
+                    if let Err(err) = automove::check_nested_destination(&rule) {
+                        problems.push((context.clone(), err.to_string()));
+                    }
+                }
+            }
+            Err(err) => problems.push((context, err.to_string())),
+        }
+    }
+
+    for (context, problem) in &problems {
+        if config.settings.color {
+            eprintln!("{} {} {problem}", "(!)".bold(), context.red());
+        } else {
+            eprintln!("(!) {context}: {problem}");
+        }
+    }
+    if !problems.is_empty() {
+        eprintln!();
+        bail!("{} problems were found in the config.", problems.len());
+    }
+
+    let msg = format!(
+        "Config is valid ({} directories, {} auto-move rules).",
+        config.directories.len(),
+        config.automove.rules.len()
+    );
+    if config.settings.color {
+        println!("{}", msg.green());
+    } else {
+        println!("{msg}");
+    }
+    Ok(())
+}
@@ -1,64 +1,406 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
 
+use anyhow::{bail, format_err};
+use serde::de::{self, DeserializeOwned};
 use serde::Deserialize;
 
+use crate::rules::FileType;
+
 /// Configuration file
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
+    /// Other config files (globs relative to the config directory) to take
+    /// directories and auto-move rules from
+    #[serde(default)]
+    pub include: Vec<String>,
+
     pub settings: Settings,
 
-    #[serde(rename = "dir")]
+    #[serde(rename = "dir", default)]
     pub directories: HashMap<String, DirectoryConfig>,
 
     pub automove: AutoMoveConfig,
 }
 
+/// A config file included from the main config
+#[derive(Clone, Debug, Deserialize)]
+struct IncludedConfig {
+    #[serde(rename = "dir", default)]
+    directories: HashMap<String, DirectoryConfig>,
+
+    #[serde(default)]
+    automove: IncludedAutoMoveConfig,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct IncludedAutoMoveConfig {
+    #[serde(default)]
+    rules: Vec<AutoMoveRule>,
+}
+
 /// General application settings
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Settings {
-    /// Whether to use colors in the terminal output
-    #[serde(default = "default_true")]
+    /// Whether to use colors in the terminal output, as set in the config file.
+    /// If unset, it depends on `NO_COLOR` and whether stdout is a terminal
+    #[serde(rename = "color", default)]
+    pub color_setting: Option<bool>,
+    /// Whether to use colors in the terminal output, resolved at startup
+    #[serde(skip)]
     pub color: bool,
+    /// Whether to leave out informational messages and warnings, set with `--quiet`
+    #[serde(skip)]
+    pub quiet: bool,
     /// Whether to use unicode in terminal output
     #[serde(rename = "use-unicode", default = "default_true")]
     pub unicode: bool,
+    /// Symbols shown when `use-unicode` is set
+    #[serde(default)]
+    pub glyphs: Glyphs,
     /// Whether to hide directories (and automove rules) when they are all good
     #[serde(default = "default_true")]
     pub hide_ok_directories: bool,
+    /// How many files to list per report line before truncating, 0 meaning no limit
+    #[serde(default = "default_max_display")]
+    pub max_display: usize,
+    /// Whether checks classify symlinks by the file they point to rather than as symlinks
+    #[serde(default = "default_true")]
+    pub follow_symlinks: bool,
+    /// Absolute paths or globs of entries never reported by checks, whatever the rules
+    #[serde(default)]
+    pub ignore_paths: Vec<String>,
+    /// Order of the directories files were moved to in auto-move reports
+    #[serde(default)]
+    pub moved_to_order: MovedToOrder,
+}
+
+/// Order of the directories files were moved to in auto-move reports
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MovedToOrder {
+    /// Alphabetically by path
+    #[default]
+    Path,
+    /// Most moved files first
+    Count,
+}
+
+/// Symbols of the terminal output when `use-unicode` is set, Nerd Font icons by default
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Glyphs {
+    /// Marks directories and rules that are all good
+    pub checkmark: String,
+    /// Marks directories with misplaced files
+    pub xmark: String,
+    /// Separates the counts of a rule's report
+    pub dot: String,
+    /// Starts the summary of a check
+    pub search: String,
+}
+
+impl Default for Glyphs {
+    fn default() -> Self {
+        Self {
+            checkmark: "\u{f00c}".to_string(),
+            xmark: "\u{f467}".to_string(),
+            dot: "\u{f444}".to_string(),
+            search: "\u{f002}".to_string(),
+        }
+    }
 }
 
 /// Configuration for a directory
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 #[serde(default, rename_all = "kebab-case")]
-#[allow(clippy::module_name_repetitions)]
+#[allow(clippy::module_name_repetitions, clippy::struct_excessive_bools)]
 pub struct DirectoryConfig {
+    /// Whether to check this directory
+    pub enabled: bool,
+
     pub recursive: bool,
+    /// How many levels below the directory to check when `recursive` is `true`
+    pub max_depth: Option<usize>,
     #[serde(alias = "recursive-ignore")]
     pub recursive_ignore_children: Vec<MatchRule>,
+    /// Whether to descend into symlinked directories when `recursive` is `true`
+    pub recursive_follow_symlinks: bool,
+    /// Whether not to descend into hidden directories when `recursive` is `true`
+    pub skip_hidden_dirs: bool,
+    /// Whether to stay on the filesystem of the directory when `recursive` is `true`
+    pub one_filesystem: bool,
+    /// Whether to leave out entries ignored by `.gitignore` files, and `.git` directories
+    pub respect_gitignore: bool,
 
     pub allowed_dirs: Option<Vec<MatchRule>>,
     pub allowed_files: Option<Vec<MatchRule>>,
+    /// Files and directories that are always misplaced, even if otherwise allowed
+    pub disallowed: Vec<MatchRule>,
+
+    /// Whether filename rules should ignore case
+    pub case_insensitive: bool,
+
+    /// Overrides the `color` setting for the report of this directory
+    pub color: Option<bool>,
+    /// Overrides the `use-unicode` setting for the report of this directory
+    #[serde(rename = "use-unicode")]
+    pub unicode: Option<bool>,
+
+    /// Whether to reuse the results of previous runs for directories whose entries haven't changed
+    pub cache: bool,
+    /// Overrides the `follow-symlinks` setting for this directory
+    pub follow_symlinks: Option<bool>,
+}
+
+impl Default for DirectoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            recursive: false,
+            max_depth: None,
+            recursive_ignore_children: Vec::new(),
+            recursive_follow_symlinks: false,
+            skip_hidden_dirs: false,
+            one_filesystem: false,
+            respect_gitignore: false,
+            allowed_dirs: None,
+            allowed_files: None,
+            disallowed: Vec::new(),
+            case_insensitive: false,
+            color: None,
+            unicode: None,
+            cache: false,
+            follow_symlinks: None,
+        }
+    }
 }
 
 /// A rule to check if the filename matches
+#[derive(Clone, Debug)]
+pub enum MatchRule {
+    Name {
+        name: String,
+    },
+    Extension {
+        ext: String,
+    },
+    Pattern {
+        pattern: String,
+    },
+    Glob {
+        glob: String,
+    },
+    /// Matches files whose path relative to the directory (or the rule's parent), with `/`
+    /// separators, matches the regex
+    PathPattern {
+        path_pattern: String,
+    },
+    ModifiedWithin {
+        days: u64,
+    },
+    ModifiedOlderThan {
+        days: u64,
+    },
+    /// Matches files last read more than `days` ago
+    AccessedOlderThan {
+        days: u64,
+    },
+    /// Matches files of the given type
+    Type {
+        file_type: FileType,
+    },
+    /// Matches filenames with a number of characters within the given bounds, inclusive
+    NameLength {
+        length: LengthRange,
+    },
+    /// Matches files that match none of the nested rules
+    Not {
+        not: Vec<MatchRule>,
+    },
+    /// Matches files that match every nested rule
+    All {
+        all: Vec<MatchRule>,
+    },
+    /// Matches empty files and directories, or non-empty ones when `false`
+    Empty {
+        empty: bool,
+    },
+    /// Matches files whose permission bits selected by `mask` (defaulting to `mode`)
+    /// are `mode`. Only matches on Unix
+    Mode {
+        mode: u32,
+        mask: Option<u32>,
+    },
+    /// Matches files whose content type (sniffed from their first bytes) is `mime`,
+    /// which may end with `/*` to match a whole category
+    Mime {
+        mime: String,
+    },
+    /// Matches files owned by a user, by uid or name. Only matches on Unix
+    Owner {
+        uid: UnixId,
+    },
+    /// Matches files owned by a group, by gid or name. Only matches on Unix
+    Group {
+        gid: UnixId,
+    },
+}
+
+/// Key of a match rule table, which tells the kind of rule
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "kebab-case")]
+enum MatchRuleKey {
+    Name,
+    Ext,
+    Pattern,
+    Glob,
+    PathPattern,
+    ModifiedWithin,
+    ModifiedOlderThan,
+    AccessedOlderThan,
+    Type,
+    NameLength,
+    Not,
+    All,
+    Empty,
+    Mode,
+    Mask,
+    Mime,
+    Uid,
+    Gid,
+}
+
+impl<'de> Deserialize<'de> for MatchRule {
+    /// Reads a rule from a table with a single key (`mode` also taking a `mask`), so that
+    /// mistakes are reported on the rule itself rather than on the table holding it
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MatchRuleVisitor;
+
+        impl<'de> de::Visitor<'de> for MatchRuleVisitor {
+            type Value = MatchRule;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a match rule, e.g. { ext = \"pdf\" }")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<MatchRule, A::Error> {
+                let mut rule = None;
+                let mut mask = None;
+                while let Some(key) = map.next_key()? {
+                    let next = match key {
+                        MatchRuleKey::Mask => {
+                            mask = Some(map.next_value()?);
+                            continue;
+                        }
+                        MatchRuleKey::Name => MatchRule::Name {
+                            name: map.next_value()?,
+                        },
+                        MatchRuleKey::Ext => MatchRule::Extension {
+                            ext: map.next_value()?,
+                        },
+                        MatchRuleKey::Pattern => MatchRule::Pattern {
+                            pattern: map.next_value()?,
+                        },
+                        MatchRuleKey::Glob => MatchRule::Glob {
+                            glob: map.next_value()?,
+                        },
+                        MatchRuleKey::PathPattern => MatchRule::PathPattern {
+                            path_pattern: map.next_value()?,
+                        },
+                        MatchRuleKey::ModifiedWithin => MatchRule::ModifiedWithin {
+                            days: map.next_value()?,
+                        },
+                        MatchRuleKey::ModifiedOlderThan => MatchRule::ModifiedOlderThan {
+                            days: map.next_value()?,
+                        },
+                        MatchRuleKey::AccessedOlderThan => MatchRule::AccessedOlderThan {
+                            days: map.next_value()?,
+                        },
+                        MatchRuleKey::Type => MatchRule::Type {
+                            file_type: map.next_value()?,
+                        },
+                        MatchRuleKey::NameLength => MatchRule::NameLength {
+                            length: map.next_value()?,
+                        },
+                        MatchRuleKey::Not => MatchRule::Not {
+                            not: map.next_value()?,
+                        },
+                        MatchRuleKey::All => MatchRule::All {
+                            all: map.next_value()?,
+                        },
+                        MatchRuleKey::Empty => MatchRule::Empty {
+                            empty: map.next_value()?,
+                        },
+                        MatchRuleKey::Mode => MatchRule::Mode {
+                            mode: map.next_value()?,
+                            mask: None,
+                        },
+                        MatchRuleKey::Mime => MatchRule::Mime {
+                            mime: map.next_value()?,
+                        },
+                        MatchRuleKey::Uid => MatchRule::Owner {
+                            uid: map.next_value()?,
+                        },
+                        MatchRuleKey::Gid => MatchRule::Group {
+                            gid: map.next_value()?,
+                        },
+                    };
+                    if rule.replace(next).is_some() {
+                        return Err(de::Error::custom(
+                            "a match rule takes a single key, combine rules with `all` instead",
+                        ));
+                    }
+                }
+                match (rule, mask) {
+                    (Some(MatchRule::Mode { mode, .. }), mask) => {
+                        Ok(MatchRule::Mode { mode, mask })
+                    }
+                    (Some(_), Some(_)) => Err(de::Error::custom("`mask` only goes with `mode`")),
+                    (Some(rule), None) => Ok(rule),
+                    (None, _) => Err(de::Error::custom(
+                        "empty match rule, expected a key such as `ext` or `name`",
+                    )),
+                }
+            }
+        }
+
+        deserializer.deserialize_map(MatchRuleVisitor)
+    }
+}
+
+/// Inclusive bounds on a length, either of which may be omitted
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub struct LengthRange {
+    pub min: Option<usize>,
+    pub max: Option<usize>,
+}
+
+/// A Unix user or group, by numeric id or name
 #[derive(Clone, Debug, Deserialize)]
 #[serde(untagged)]
-pub enum MatchRule {
-    Name { name: String },
-    Extension { ext: String },
-    Pattern { pattern: String },
+pub enum UnixId {
+    Id(u32),
+    Name(String),
 }
 
 /// Auto-Move configuration
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
-#[allow(clippy::module_name_repetitions)]
+#[allow(clippy::module_name_repetitions, clippy::struct_excessive_bools)]
 pub struct AutoMoveConfig {
     /// Whether to show "scripts may slow down the execution" warning
     #[serde(default)]
     pub script_warning: bool,
 
+    /// Seconds after which a to-script is killed, 0 meaning no timeout
+    #[serde(default = "default_script_timeout")]
+    pub script_timeout: u64,
+
     /// Level of auto-move report info
     #[serde(default)]
     pub report_info: AutoMoveReportInfo,
@@ -67,17 +409,56 @@ pub struct AutoMoveConfig {
     #[serde(default)]
     pub force_dry_run: bool,
 
+    /// Whether auto-move only moves files with `--apply`, doing a dry run otherwise
+    #[serde(default)]
+    pub safe_mode: bool,
+
     // Here be dragons
     #[serde(default)]
     pub allow_overwrite: bool,
 
+    /// Existing destinations that may be overwritten even without `allow-overwrite`
+    #[serde(default)]
+    pub overwrite_if: Vec<MatchRule>,
+
+    /// Destination of the rules that have neither a `to` nor a `route`
+    pub default_to: Option<String>,
+
+    /// What to do when the destination of a file already exists
+    #[serde(default)]
+    pub on_conflict: ConflictStrategy,
+
+    /// Filename given to files renamed by `on-conflict = "rename"`, with `{stem}`, `{ext}`,
+    /// `{counter}` and `{hash8}` placeholders
+    #[serde(default = "default_rename_template")]
+    pub rename_template: String,
+
+    /// Whether to remove the directories left empty by moves
+    #[serde(default)]
+    pub remove_empty_dirs: bool,
+
+    /// Whether to leave files skipped by `skip-identical` in place instead of deleting them
+    #[serde(default)]
+    pub keep_identical_source: bool,
+
+    /// Whether to send a desktop notification summarizing each run
+    #[serde(default)]
+    pub notify: bool,
+
+    /// File to append a human-readable line to for every move
+    pub log_file: Option<String>,
+
+    /// Size in KiB past which the log file is rotated, 0 meaning no limit
+    #[serde(default = "default_log_max_size")]
+    pub log_max_size: u64,
+
     #[serde(default)]
     pub rules: Vec<AutoMoveRule>,
 }
 
 /// What kind of information about Auto-Move files to print
 /// at the end of a report
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum AutoMoveReportInfo {
     /// Disable this extra info
@@ -85,13 +466,36 @@ pub enum AutoMoveReportInfo {
     /// Display if any file can be automatically moved
     Any,
     /// Display the number of files that can be automatically moved
+    #[default]
     Count,
 }
 
+/// Strategy to apply when a file would be moved to an existing path
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictStrategy {
+    /// Report an error, unless `allow-overwrite` is set
+    #[default]
+    Error,
+    /// Rename the file with `rename-template` until it is free, appending ` (1)`, ` (2)`, etc.
+    /// by default
+    Rename,
+    /// Send the existing file to the system trash before moving
+    Trash,
+    /// Overwrite the existing file only if it is older than the moved one, otherwise leave the file in place
+    KeepNewer,
+    /// Don't move files identical to the existing one, and report an error otherwise
+    SkipIdentical,
+}
+
 /// A rule to automatically move files
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
+#[allow(clippy::struct_excessive_bools)]
 pub struct AutoMoveRule {
+    /// Whether this rule is used
+    #[serde(default = "default_true")]
+    pub enabled: bool,
     /// Custom rule name
     pub name: Option<String>,
     /// Parent directory
@@ -99,18 +503,301 @@ pub struct AutoMoveRule {
     /// File matcher (applied of contents of parent directory)
     #[serde(alias = "match")]
     pub match_rules: Vec<MatchRule>,
-    /// Which directory to move it to
+    /// Which directory to move it to, `automove.default-to` if empty
+    #[serde(default)]
     pub to: String,
+    /// Which directory to move files to by extension, instead of `to`
+    #[serde(default)]
+    pub route: HashMap<String, String>,
+    /// Whether `to` and `route` directories are relative to the directory of each file
+    #[serde(default)]
+    pub to_relative: bool,
+    /// Whether to put files in a subdirectory of `to` named after their extension
+    #[serde(default)]
+    pub group_by_extension: bool,
+    /// Subdirectory for files without an extension when `group_by_extension` is `true`
+    #[serde(default = "default_no_extension_dir")]
+    pub no_extension_dir: String,
     /// Path to a script that gives the output filename
     pub to_script: Option<String>,
+    /// Whether to call `to_script` once with all files on stdin
+    #[serde(default)]
+    pub to_script_batch: bool,
+    /// Exit code of `to_script` meaning the file is left in place, rather than an error
+    pub to_script_skip_code: Option<i32>,
+    /// Whether filename rules should ignore case
+    #[serde(default)]
+    pub case_insensitive: bool,
+    /// Regex based renaming
+    pub rename: Option<RenameConfig>,
+    /// Whether to also move files from subdirectories, keeping their subpath
+    #[serde(default)]
+    pub recursive: bool,
+    /// Subdirectories not to descend into when `recursive` is `true`
+    #[serde(default, alias = "recursive-ignore")]
+    pub recursive_ignore_children: Vec<MatchRule>,
+    /// Whether matching directories are moved along with their contents
+    #[serde(default)]
+    pub move_directories: bool,
 }
 
-impl Default for AutoMoveReportInfo {
-    fn default() -> Self {
-        Self::Count
+/// Renaming of moved files based on a regex
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RenameConfig {
+    /// Regex the original filename needs to match
+    pub from: String,
+    /// New filename, which can refer to capture groups of `from` (`$1`, `${name}`)
+    pub to_name: String,
+}
+
+impl fmt::Display for MatchRule {
+    /// Formats the rule as written in the config, e.g. `ext = "pdf"`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name { name } => write!(f, "name = {name:?}"),
+            Self::Extension { ext } => write!(f, "ext = {ext:?}"),
+            Self::Pattern { pattern } => write!(f, "pattern = {pattern:?}"),
+            Self::Glob { glob } => write!(f, "glob = {glob:?}"),
+            Self::PathPattern { path_pattern } => write!(f, "path-pattern = {path_pattern:?}"),
+            Self::ModifiedWithin { days } => write!(f, "modified-within = {days}"),
+            Self::ModifiedOlderThan { days } => write!(f, "modified-older-than = {days}"),
+            Self::AccessedOlderThan { days } => write!(f, "accessed-older-than = {days}"),
+            Self::NameLength { length } => match (length.min, length.max) {
+                (Some(min), Some(max)) => write!(f, "name-length = {min}..{max}"),
+                (Some(min), None) => write!(f, "name-length >= {min}"),
+                (None, Some(max)) => write!(f, "name-length <= {max}"),
+                (None, None) => write!(f, "name-length = any"),
+            },
+            Self::Type { file_type } => write!(f, "type = \"{file_type}\""),
+            Self::Not { not } => {
+                let nested = not.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "not [{}]", nested.join(", "))
+            }
+            Self::All { all } => {
+                let nested = all.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "all [{}]", nested.join(", "))
+            }
+            Self::Empty { empty } => write!(f, "empty = {empty}"),
+            Self::Mode { mode, mask: None } => write!(f, "mode = {mode:#o}"),
+            Self::Mode {
+                mode,
+                mask: Some(mask),
+            } => write!(f, "mode = {mode:#o} (mask {mask:#o})"),
+            Self::Mime { mime } => write!(f, "mime = {mime:?}"),
+            Self::Owner { uid } => write!(f, "uid = {uid}"),
+            Self::Group { gid } => write!(f, "gid = {gid}"),
+        }
     }
 }
 
+impl fmt::Display for UnixId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Id(id) => write!(f, "{id}"),
+            Self::Name(name) => write!(f, "{name:?}"),
+        }
+    }
+}
+
+impl Config {
+    /// Reads a config file along with the files it includes.
+    ///
+    /// Environment overrides and the resolved `color` setting are left to the caller.
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format_err!("Could not read config file: {err}"))?;
+        let mut config: Self = parse_toml(&contents, path)?;
+        config.merge_includes(path.parent().unwrap_or(Path::new("")))?;
+        Ok(config)
+    }
+
+    /// Merges directories and auto-move rules from included config files.
+    ///
+    /// A directory configured in several files is an error.
+    pub fn merge_includes(&mut self, config_dir: &Path) -> anyhow::Result<()> {
+        let mut dir_sources = HashMap::new();
+        for pattern in &self.include {
+            let expanded = shellexpand::env(pattern)?;
+            let full_pattern = config_dir.join(expanded.as_ref());
+            let full_pattern = full_pattern.to_string_lossy();
+            let mut paths = glob::glob(&full_pattern)
+                .map_err(|err| format_err!("Invalid include pattern '{pattern}': {err}"))?
+                .collect::<Result<Vec<_>, _>>()?;
+            if paths.is_empty() && !pattern.contains(['*', '?', '[']) {
+                bail!("Included config file '{pattern}' does not exist");
+            }
+            paths.sort();
+
+            for path in paths {
+                let contents = fs::read_to_string(&path).map_err(|err| {
+                    format_err!(
+                        "Could not read included config {}: {err}",
+                        path.to_string_lossy()
+                    )
+                })?;
+                let included: IncludedConfig = parse_toml(&contents, &path)?;
+                for (dir, dir_config) in included.directories {
+                    if self.directories.contains_key(&dir) {
+                        let previous = dir_sources
+                            .get(&dir)
+                            .map_or("the main config".to_string(), |source: &String| {
+                                source.clone()
+                            });
+                        bail!(
+                            "Directory '{dir}' is configured in both {previous} and {}",
+                            path.to_string_lossy()
+                        );
+                    }
+                    dir_sources.insert(dir.clone(), path.to_string_lossy().to_string());
+                    self.directories.insert(dir, dir_config);
+                }
+                self.automove.rules.extend(included.automove.rules);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Settings {
+    /// Overrides settings with the `SHINYDIR_<SETTING>` environment variables, e.g.
+    /// `SHINYDIR_USE_UNICODE=0` for `use-unicode = false`.
+    ///
+    /// `var` looks up a variable, it is only a parameter for tests.
+    pub fn apply_env_overrides(
+        &mut self,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> anyhow::Result<()> {
+        if let Some(value) = var("SHINYDIR_COLOR") {
+            self.color_setting = Some(parse_env_bool("SHINYDIR_COLOR", &value)?);
+        }
+        if let Some(value) = var("SHINYDIR_USE_UNICODE") {
+            self.unicode = parse_env_bool("SHINYDIR_USE_UNICODE", &value)?;
+        }
+        if let Some(value) = var("SHINYDIR_HIDE_OK_DIRECTORIES") {
+            self.hide_ok_directories = parse_env_bool("SHINYDIR_HIDE_OK_DIRECTORIES", &value)?;
+        }
+        if let Some(value) = var("SHINYDIR_MAX_DISPLAY") {
+            self.max_display = value.trim().parse().map_err(|_| {
+                format_err!("Invalid SHINYDIR_MAX_DISPLAY '{value}', expected a number")
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a boolean environment variable, accepting `1`/`0`, `true`/`false`, `yes`/`no` and `on`/`off`
+fn parse_env_bool(name: &str, value: &str) -> anyhow::Result<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => bail!("Invalid {name} '{value}', expected 1 or 0"),
+    }
+}
+
+/// Parses a config file, prefixing errors with its path and the line and column at fault
+pub fn parse_toml<T: DeserializeOwned>(contents: &str, path: &Path) -> anyhow::Result<T> {
+    toml::from_str(contents).map_err(|err| {
+        let mut message = err.to_string();
+        let location = match err.line_col() {
+            Some((line, column)) => {
+                let (line, column) = (line + 1, column + 1);
+                // the location moves to the front, where editors and terminals pick it up
+                let suffix = format!(" at line {line} column {column}");
+                if message.ends_with(&suffix) {
+                    message.truncate(message.len() - suffix.len());
+                }
+                format!("{}:{line}:{column}", path.to_string_lossy())
+            }
+            None => path.to_string_lossy().to_string(),
+        };
+        // set the key apart from the sentence it is glued to
+        if let Some((sentence, key)) = message.rsplit_once(" for key `") {
+            if let Some(key) = key.strip_suffix('`') {
+                message = format!("{sentence} (in `{key}`)");
+            }
+        }
+        format_err!("{location}: {message}")
+    })
+}
+
+fn default_no_extension_dir() -> String {
+    "no-extension".to_string()
+}
+
 fn default_true() -> bool {
     true
 }
+
+fn default_max_display() -> usize {
+    20
+}
+
+fn default_log_max_size() -> u64 {
+    1024
+}
+
+fn default_rename_template() -> String {
+    crate::automove::DEFAULT_RENAME_TEMPLATE.to_string()
+}
+
+fn default_script_timeout() -> u64 {
+    30
+}
+
+#[test]
+fn test_env_overrides() {
+    let mut config: Config = toml::from_str("[settings]\n[automove]\n").unwrap();
+    let vars = HashMap::from([
+        ("SHINYDIR_COLOR", "0"),
+        ("SHINYDIR_USE_UNICODE", "false"),
+        ("SHINYDIR_MAX_DISPLAY", "5"),
+    ]);
+    config
+        .settings
+        .apply_env_overrides(|name| vars.get(name).map(ToString::to_string))
+        .unwrap();
+    assert_eq!(config.settings.color_setting, Some(false));
+    assert!(!config.settings.unicode);
+    assert!(config.settings.hide_ok_directories);
+    assert_eq!(config.settings.max_display, 5);
+
+    let err = config
+        .settings
+        .apply_env_overrides(|name| (name == "SHINYDIR_COLOR").then(|| "maybe".to_string()))
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid SHINYDIR_COLOR 'maybe', expected 1 or 0"
+    );
+}
+
+#[test]
+fn test_parse_toml_location() {
+    let err = parse_toml::<Config>(
+        "[settings]\n[automove]\n[dir.\"/tmp\"]\nallowed-files = [{ extt = \"png\" }]\n",
+        Path::new("shinydir.toml"),
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("shinydir.toml:4:18: unknown field `extt`, expected one of `name`, `ext`,"));
+
+    let err = parse_toml::<Config>(
+        "[settings]\n[automove]\n[dir.\"/tmp\"]\nallowed-files = [\n  { ext = \"png\" },\n  { ext = \"pdf\", name = \"a\" },\n]\n",
+        Path::new("shinydir.toml"),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "shinydir.toml:6:3: a match rule takes a single key, combine rules with `all` instead (in `dir./tmp.allowed-files`)"
+    );
+}
+
+#[test]
+fn test_glyphs() {
+    let config: Config =
+        toml::from_str("[settings]\nglyphs = { checkmark = \"v\" }\n[automove]\n").unwrap();
+    assert_eq!(config.settings.glyphs.checkmark, "v");
+    assert_eq!(config.settings.glyphs.xmark, Glyphs::default().xmark);
+}
@@ -0,0 +1,189 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+/// Journal of the moves performed during an auto-move run, used to undo it.
+///
+/// Each move is appended as its own JSON line as soon as it happens, so an
+/// interrupted run still leaves a usable record.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    /// Opened lazily so runs that move nothing don't leave empty journals
+    file: Option<File>,
+}
+
+/// A move recorded in a journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Original file path
+    pub from: PathBuf,
+    /// Path the file was moved to
+    pub to: PathBuf,
+}
+
+impl Journal {
+    /// Prepares a journal for a new run
+    pub fn new(config_dir: &Path) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        Self {
+            path: journal_dir(config_dir).join(format!("{timestamp:020}.{JOURNAL_EXTENSION}")),
+            file: None,
+        }
+    }
+
+    /// Appends a successful move to the journal
+    pub fn record(&mut self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        if self.file.is_none() {
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            self.file = Some(file);
+        }
+        let mut line = serde_json::to_string(&JournalEntry {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        })?;
+        line.push('\n');
+        // a single write per line so entries are never interleaved
+        if let Some(file) = &mut self.file {
+            file.write_all(line.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Replaces every move recorded so far, removing the journal if none are left
+    pub fn replace(&mut self, entries: &[JournalEntry]) -> anyhow::Result<()> {
+        if self.file.take().is_none() && entries.is_empty() {
+            return Ok(());
+        }
+        rewrite(&self.path, entries)
+    }
+}
+
+/// Extension of the journals of runs that can be undone
+const JOURNAL_EXTENSION: &str = "jsonl";
+
+/// Extension of the journals of runs that were undone
+const UNDONE_EXTENSION: &str = "undone";
+
+/// Directory in which journals are stored
+pub fn journal_dir(config_dir: &Path) -> PathBuf {
+    config_dir.join("journal")
+}
+
+/// Returns the path of the most recent journal of a run that wasn't undone, if any
+pub fn latest(config_dir: &Path) -> anyhow::Result<Option<PathBuf>> {
+    latest_with_extension(config_dir, &[JOURNAL_EXTENSION])
+}
+
+/// Whether the most recent run was undone, in which case [`latest`] gives an older run
+pub fn is_last_run_undone(config_dir: &Path) -> anyhow::Result<bool> {
+    let latest = latest_with_extension(config_dir, &[JOURNAL_EXTENSION, UNDONE_EXTENSION])?;
+    Ok(latest.is_some_and(|path| path.extension().is_some_and(|ext| ext == UNDONE_EXTENSION)))
+}
+
+/// Marks the run of a journal as undone. The journal is kept with another extension, so that
+/// the next undo doesn't silently walk back to the run before
+pub fn mark_undone(path: &Path) -> anyhow::Result<()> {
+    fs::rename(path, path.with_extension(UNDONE_EXTENSION))?;
+    Ok(())
+}
+
+/// Returns the path of the most recent file of the journal directory with one of `extensions`
+fn latest_with_extension(
+    config_dir: &Path,
+    extensions: &[&str],
+) -> anyhow::Result<Option<PathBuf>> {
+    let dir = journal_dir(config_dir);
+    if !dir.try_exists()? {
+        return Ok(None);
+    }
+    // file stems are zero-padded timestamps, so they sort chronologically
+    let latest = fs::read_dir(&dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| extensions.iter().any(|extension| ext == *extension))
+        })
+        .max_by(|a, b| a.file_stem().cmp(&b.file_stem()));
+    Ok(latest)
+}
+
+/// Reads all entries of a journal.
+///
+/// A malformed last line is ignored as it comes from an interrupted write.
+pub fn read(path: &Path) -> anyhow::Result<Vec<JournalEntry>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Could not read journal {}", path.to_string_lossy()))?;
+    let lines = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>();
+    let mut entries = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) if i == lines.len() - 1 => {}
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!(
+                        "Malformed journal {} at line {}",
+                        path.to_string_lossy(),
+                        i + 1
+                    )
+                })
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Replaces the contents of a journal, removing it if there are no entries left
+pub fn rewrite(path: &Path, entries: &[JournalEntry]) -> anyhow::Result<()> {
+    if entries.is_empty() {
+        fs::remove_file(path)?;
+        return Ok(());
+    }
+    let mut contents = String::new();
+    for entry in entries {
+        contents.push_str(&serde_json::to_string(entry)?);
+        contents.push('\n');
+    }
+    let tmp_path = path.with_extension("jsonl.tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[test]
+fn test_undone_runs() {
+    let config_dir =
+        std::env::temp_dir().join(format!("shinydir-journal-undone-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&config_dir);
+    let dir = journal_dir(&config_dir);
+    fs::create_dir_all(&dir).unwrap();
+    let older = dir.join("00000000000000000001.jsonl");
+    let newer = dir.join("00000000000000000002.jsonl");
+    fs::write(&older, "").unwrap();
+    fs::write(&newer, "").unwrap();
+    assert_eq!(Some(newer.clone()), latest(&config_dir).unwrap());
+    assert!(!is_last_run_undone(&config_dir).unwrap());
+
+    mark_undone(&newer).unwrap();
+    assert_eq!(Some(older), latest(&config_dir).unwrap());
+    assert!(is_last_run_undone(&config_dir).unwrap());
+    fs::remove_dir_all(config_dir).unwrap();
+}
@@ -0,0 +1,19 @@
+//! Core of shinydir: checking directories for misplaced files and moving them where they belong.
+//!
+//! The `shd` binary is a command line interface over this library. A typical use is to read a
+//! [`config::Config`] with [`config::Config::from_path`], then run a [`checker::Checker`] or an
+//! [`automove::AutoMove`] set up from it.
+#![deny(clippy::pedantic)]
+#![allow(
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc,
+    clippy::must_use_candidate
+)]
+
+pub mod automove;
+pub mod checker;
+pub mod config;
+pub mod journal;
+pub mod move_log;
+pub mod rules;
+pub mod scan_cache;
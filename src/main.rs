@@ -2,27 +2,33 @@
 
 use std::env;
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
 
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 
 use anyhow::Context;
-use cli::{Cli, Commands};
+use cli::{Cli, ColorChoice, Commands};
 use config::Config;
+use shinydir::{automove, checker, config, journal, move_log, scan_cache};
 
-mod automove;
-mod checker;
 mod cli;
 mod commands;
-mod config;
-mod rules;
+
+/// Bundled config, copied when no config file exists
+const DEFAULT_CONFIG: &str = include_str!("../shinydir.toml");
+
+/// Config file path meaning the config is read from stdin
+const STDIN_CONFIG: &str = "-";
+
+/// Name of the project config file, looked up in the current directory and its ancestors
+const LOCAL_CONFIG: &str = ".shinydir.toml";
 
 fn main() {
     match run() {
         Ok(()) => (),
         Err(e) => {
-            eprintln!("{}", e);
+            eprintln!("{e}");
             std::process::exit(1);
         }
     }
@@ -31,63 +37,184 @@ fn main() {
 fn run() -> anyhow::Result<()> {
     let cli: Cli = Cli::parse();
 
+    // Commands that don't need a config
+    if let Commands::Completions { shell } = cli.command {
+        // generated in memory, as clap_complete panics on write errors such as a closed pipe
+        let mut script = Vec::new();
+        clap_complete::generate(
+            shell,
+            &mut Cli::command(),
+            env!("CARGO_BIN_NAME"),
+            &mut script,
+        );
+        return match io::stdout().write_all(&script) {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            res => Ok(res?),
+        };
+    }
+
     set_missing_env_vars();
 
+    if let Commands::Init(args) = &cli.command {
+        let config_path = match configured_config_file_path(&cli) {
+            Some(path) if path == Path::new(STDIN_CONFIG) => {
+                anyhow::bail!("init can't write the config to stdin, give a file path instead")
+            }
+            Some(path) => path,
+            None => default_config_file_path()?,
+        };
+        return commands::init::execute(&config_path, DEFAULT_CONFIG, args);
+    }
+
     // Read config
     let config_path = find_config_file_path(&cli)?;
 
-    let config_contents = fs::read_to_string(&config_path)
-        .map_err(|err| anyhow::format_err!("Could not read config file: {}", err))?;
-    let config_dir = config_path
-        .parent()
-        .map_or(PathBuf::new(), Path::to_path_buf);
-    let config: Config = toml::from_str(&config_contents)?;
+    let (mut config, config_dir) = if config_path == Path::new(STDIN_CONFIG) {
+        check_stdin_available(&cli.command)?;
+        let mut contents = String::new();
+        io::stdin()
+            .read_to_string(&mut contents)
+            .map_err(|err| anyhow::format_err!("Could not read config from stdin: {err}"))?;
+        let config_dir = env::current_dir()?;
+        let mut config: Config = config::parse_toml(&contents, Path::new("<stdin>"))?;
+        config.merge_includes(&config_dir)?;
+        (config, config_dir)
+    } else {
+        let config_dir = config_path
+            .parent()
+            .map_or(PathBuf::new(), Path::to_path_buf);
+        (Config::from_path(&config_path)?, config_dir)
+    };
+    config
+        .settings
+        .apply_env_overrides(|name| env::var(name).ok())?;
+    config.settings.color = resolve_color(cli.color, config.settings.color_setting);
+    colored::control::set_override(config.settings.color);
+    config.settings.quiet = cli.quiet;
+    init_tracing(cli.verbose, config.settings.color);
 
     // Run command
     match cli.command {
-        Commands::Check { target, list } => {
-            commands::check::execute(&config, &config_dir, target, list)
+        Commands::Check(args) => commands::check::execute(&config, &config_dir, &args),
+        Commands::AutoMove(args) => commands::automove::execute(&config, &config_dir, args),
+        Commands::Undo { previous } => commands::undo::execute(&config, &config_dir, previous),
+        Commands::Validate => commands::validate::execute(&config, &config_dir),
+        Commands::Config { command } => commands::config::execute(&config, &config_dir, &command),
+        Commands::Report(args) => commands::report::execute(&config, &config_dir, &args),
+        Commands::Tidy(args) => commands::tidy::execute(&config, &config_dir, &args),
+        Commands::Completions { .. } | Commands::Init(_) => {
+            unreachable!("handled before reading config")
         }
-        Commands::AutoMove {
-            target,
-            list,
-            dry_run,
-        } => commands::automove::execute(&config, &config_dir, target, list, dry_run),
     }?;
 
     Ok(())
 }
 
+/// Logs to stderr at a level depending on how many times `--verbose` was given
+fn init_tracing(verbose: u8, color: bool) {
+    let level = match verbose {
+        0 => return,
+        1 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .with_ansi(color)
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
+/// Decides whether to use colors, in order of precedence: CLI flag, config setting,
+/// `NO_COLOR` environment variable and whether stdout is a terminal
+fn resolve_color(choice: ColorChoice, setting: Option<bool>) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => setting.unwrap_or_else(|| {
+            let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+            !no_color && io::stdout().is_terminal()
+        }),
+    }
+}
+
+/// Errors if the command also needs stdin, which is used up by reading the config from it
+fn check_stdin_available(command: &Commands) -> anyhow::Result<()> {
+    match command {
+        Commands::Check(args) if args.stdin => {
+            anyhow::bail!("--stdin can't be used when the config is read from stdin")
+        }
+        Commands::AutoMove(args) if args.interactive => {
+            anyhow::bail!("--interactive can't be used when the config is read from stdin")
+        }
+        Commands::Tidy(args) if !args.yes && !args.dry_run => {
+            anyhow::bail!("tidy needs --yes or --dry when the config is read from stdin")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Finds the config file, in order of precedence: `--config`, `SHINYDIR_CONFIG_FILE`,
+/// a `.shinydir.toml` in the current directory or one of its ancestors, and the user's
+/// config directory, where the default config is copied if it's missing
 fn find_config_file_path(cli: &Cli) -> anyhow::Result<PathBuf> {
-    if let Some(path) = &cli.config {
-        return Ok(path.clone());
+    if let Some(path) = configured_config_file_path(cli) {
+        return Ok(path);
     }
-    if let Ok(path) = env::var("SHINYDIR_CONFIG_FILE") {
-        Ok(PathBuf::from(path))
-    } else {
-        create_config_file()
+    if let Some(path) = find_local_config_file(&env::current_dir()?) {
+        return Ok(path);
+    }
+    let file_path = default_config_file_path()?;
+    create_config_file(&file_path)?;
+    Ok(file_path)
+}
+
+/// Returns the config file path set from the command line or environment, if any
+fn configured_config_file_path(cli: &Cli) -> Option<PathBuf> {
+    if let Some(path) = &cli.config {
+        return Some(path.clone());
     }
+    env::var("SHINYDIR_CONFIG_FILE").ok().map(PathBuf::from)
 }
 
-fn create_config_file() -> anyhow::Result<PathBuf> {
+/// Returns the closest `.shinydir.toml` from `dir` up to the root, if any
+fn find_local_config_file(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(LOCAL_CONFIG))
+        .find(|path| path.is_file())
+}
+
+/// Returns the config file path in the user's config directory, `$XDG_CONFIG_HOME/shinydir`
+/// when it is set (on any platform)
+fn default_config_file_path() -> anyhow::Result<PathBuf> {
+    if let Some(config_home) = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+    {
+        return Ok(config_home.join("shinydir").join("shinydir.toml"));
+    }
     let project = directories::ProjectDirs::from("", "", "Shiny Dir")
         .with_context(|| "unable to find config directory")?;
-    let file_path = project.config_dir().join("shinydir.toml");
+    Ok(project.config_dir().join("shinydir.toml"))
+}
+
+fn create_config_file(file_path: &Path) -> anyhow::Result<()> {
     if !file_path.try_exists().unwrap_or(true) {
         eprintln!(
-            "Copying default configuration to {:?} because no config file was found...",
-            file_path
+            "Copying default configuration to {} because no config file was found (run `{} init` to create a tailored one instead)...",
+            file_path.display(),
+            env!("CARGO_BIN_NAME")
         );
         if let Some(parent) = file_path.parent() {
             fs::create_dir_all(parent)?;
         }
-        let mut file = File::create(&file_path)?;
-        let default_config = include_str!("../shinydir.toml").as_bytes();
-        file.write_all(default_config)?;
+        let mut file = File::create(file_path)?;
+        file.write_all(DEFAULT_CONFIG.as_bytes())?;
         file.flush()?;
         eprintln!();
     }
-    Ok(file_path)
+    Ok(())
 }
 
 /// Defines xdg directories environment variables in a cross-platform manner
@@ -113,3 +240,18 @@ fn set_env_var_if_missing(var_name: &str, value: Option<&Path>) {
         }
     }
 }
+
+#[test]
+fn test_find_local_config_file() {
+    let root = env::temp_dir().join(format!("shinydir-local-config-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("project/src")).unwrap();
+    assert_eq!(None, find_local_config_file(&root.join("project/src")));
+    fs::write(root.join(LOCAL_CONFIG), "").unwrap();
+    fs::write(root.join("project").join(LOCAL_CONFIG), "").unwrap();
+    assert_eq!(
+        Some(root.join("project").join(LOCAL_CONFIG)),
+        find_local_config_file(&root.join("project/src"))
+    );
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,122 @@
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::format_err;
+
+use crate::config::Config;
+
+/// Human-readable history of auto-move actions, appended to across runs.
+///
+/// Unlike the journal, it is never read back by shinydir. When it grows past
+/// its maximum size, it is renamed with a `.1` suffix (replacing the previous
+/// one) and a new log is started.
+#[derive(Debug)]
+pub struct MoveLog {
+    path: PathBuf,
+    /// Size in bytes past which the log is rotated, 0 meaning never
+    max_size: u64,
+    /// Opened lazily so runs that move nothing don't create or rotate the log
+    file: Option<File>,
+}
+
+impl MoveLog {
+    /// Sets up the log configured with `log-file`, if any.
+    ///
+    /// A relative path is relative to the config directory.
+    pub fn from_config(config: &Config, config_dir: &Path) -> anyhow::Result<Option<Self>> {
+        let Some(log_file) = &config.automove.log_file else {
+            return Ok(None);
+        };
+        let expanded = shellexpand::env(log_file)
+            .map_err(|err| format_err!("Invalid log file '{log_file}': {err}"))?;
+        Ok(Some(Self {
+            path: config_dir.join(expanded.as_ref()),
+            max_size: config.automove.log_max_size * 1024,
+            file: None,
+        }))
+    }
+
+    /// Appends a timestamped line for an action on a file, with details such as an error message
+    pub fn record(
+        &mut self,
+        outcome: &str,
+        from: &Path,
+        to: &Path,
+        details: Option<&str>,
+    ) -> io::Result<()> {
+        if self.file.is_none() {
+            self.rotate()?;
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            self.file = Some(file);
+        }
+        let mut line = format!(
+            "{} {outcome} {} -> {}",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            from.to_string_lossy(),
+            to.to_string_lossy()
+        );
+        if let Some(details) = details {
+            line.push_str(": ");
+            line.push_str(details);
+        }
+        line.push('\n');
+        // a single write per line so entries are never interleaved
+        if let Some(file) = &mut self.file {
+            file.write_all(line.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Moves the log aside if it is past its maximum size
+    fn rotate(&self) -> io::Result<()> {
+        if self.max_size == 0 {
+            return Ok(());
+        }
+        match fs::metadata(&self.path) {
+            Ok(md) if md.len() >= self.max_size => {
+                let mut rotated = OsString::from(self.path.as_os_str());
+                rotated.push(".1");
+                fs::rename(&self.path, rotated)
+            }
+            Ok(_) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[test]
+fn test_rotate() {
+    let dir = std::env::temp_dir().join(format!("shinydir-move-log-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("moves.log");
+    fs::write(&path, "x".repeat(2048)).unwrap();
+
+    let mut log = MoveLog {
+        path: path.clone(),
+        max_size: 1024,
+        file: None,
+    };
+    log.record("moved", Path::new("/a"), Path::new("/b"), None)
+        .unwrap();
+    log.record("error", Path::new("/c"), Path::new("/d"), Some("oops"))
+        .unwrap();
+
+    assert_eq!(fs::read(dir.join("moves.log.1")).unwrap().len(), 2048);
+    let contents = fs::read_to_string(&path).unwrap();
+    let lines = contents.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with(" moved /a -> /b"));
+    assert!(lines[1].ends_with(" error /c -> /d: oops"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,6 +1,11 @@
+use std::fmt;
 use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
 
 use regex::{RegexSet, RegexSetBuilder};
+use serde::Deserialize;
 
 use crate::config;
 
@@ -14,32 +19,120 @@ pub enum FileMatchRule {
     MergeAnd(Vec<FileMatchRule>),
     /// Any of the nested rules needs to match for the file to be valid
     MergeOr(Vec<FileMatchRule>),
+    /// The nested rule must not match for the file to be valid
+    Not(Box<FileMatchRule>),
 
     /// The file needs to be of the given type to be valid
     Type(FileType),
     /// The file's name needs to match the given [`RegexSet`] to be valid
     Name(RegexSet),
+    /// The file's path relative to the root directory needs to match the given [`RegexSet`]
+    /// to be valid
+    Path(RegexSet),
+    /// The file's last modification time needs to fit the given age to be valid
+    Age(FileAge),
+    /// The file's last access time needs to fit the given age to be valid
+    AccessAge(FileAge),
+    /// The file's name needs to have a number of characters within the given bounds to be valid
+    NameLength {
+        min: Option<usize>,
+        max: Option<usize>,
+    },
+    /// The file needs to be empty, or the directory to have no entries, to be valid
+    Empty,
+    /// The file's content type needs to match one of the given MIME types to be valid
+    Mime(Vec<String>),
+    /// The file's permission bits selected by `mask` need to be `mode` to be valid
+    #[cfg(unix)]
+    Mode { mode: u32, mask: u32 },
+    /// The file needs to be owned by the given uid to be valid
+    #[cfg(unix)]
+    Owner(u32),
+    /// The file needs to be owned by the given gid to be valid
+    #[cfg(unix)]
+    Group(u32),
 }
 
-/// A type of file
+/// An age constraint relative to the current time
 #[derive(Debug, Clone)]
+pub enum FileAge {
+    /// At most this old
+    Within(Duration),
+    /// Strictly older than this
+    OlderThan(Duration),
+}
+
+/// A type of file
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum FileType {
-    /// A simple file
+    /// A simple file, or a symlink to one
     File,
-    /// A directory
+    /// A directory, or a symlink to one
     Directory,
+    /// A symbolic link, whatever it points to
+    Symlink,
+    /// A symbolic link whose target doesn't exist
+    BrokenSymlink,
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File => write!(f, "file"),
+            Self::Directory => write!(f, "directory"),
+            Self::Symlink => write!(f, "symlink"),
+            Self::BrokenSymlink => write!(f, "broken-symlink"),
+        }
+    }
 }
 
 impl FileMatchRule {
-    /// Checks if a directory entry matches this rule
-    pub fn matches_dir_entry(&self, dir_entry: &fs::DirEntry) -> anyhow::Result<bool> {
+    /// Checks if this rule, or one nested in it, satisfies `predicate`
+    pub fn any(&self, predicate: &impl Fn(&Self) -> bool) -> bool {
+        predicate(self)
+            || match self {
+                Self::MergeAnd(rules) | Self::MergeOr(rules) => {
+                    rules.iter().any(|rule| rule.any(predicate))
+                }
+                Self::Not(rule) => rule.any(predicate),
+                _ => false,
+            }
+    }
+
+    /// Checks if this rule compares access times, which not every filesystem records
+    pub fn uses_access_time(&self) -> bool {
+        self.any(&|rule| matches!(rule, Self::AccessAge(_)))
+    }
+
+    /// Checks if this rule looks at the file itself rather than at its name or type,
+    /// so that its result can change without the parent directory being modified
+    pub fn uses_file_state(&self) -> bool {
+        self.any(&|rule| match rule {
+            Self::Age(_) | Self::AccessAge(_) | Self::Empty | Self::Mime(_) => true,
+            #[cfg(unix)]
+            Self::Mode { .. } | Self::Owner(_) | Self::Group(_) => true,
+            _ => false,
+        })
+    }
+
+    /// Checks if a directory entry matches this rule.
+    ///
+    /// Path patterns are matched against the entry's path relative to `root`.
+    /// With `follow_symlinks`, symlinks are checked as the file they point to.
+    pub fn matches_dir_entry(
+        &self,
+        dir_entry: &fs::DirEntry,
+        root: &Path,
+        follow_symlinks: bool,
+    ) -> anyhow::Result<bool> {
         let res = match self {
             Self::None => false,
 
             Self::MergeAnd(merge) => {
                 let mut res = true;
                 for rule in merge {
-                    if !rule.matches_dir_entry(dir_entry)? {
+                    if !rule.matches_dir_entry(dir_entry, root, follow_symlinks)? {
                         res = false;
                         break;
                     }
@@ -52,49 +145,268 @@ impl FileMatchRule {
                     res = true;
                 }
                 for rule in merge {
-                    if rule.matches_dir_entry(dir_entry)? {
+                    if rule.matches_dir_entry(dir_entry, root, follow_symlinks)? {
                         res = true;
                         break;
                     }
                 }
                 res
             }
+            Self::Not(rule) => !rule.matches_dir_entry(dir_entry, root, follow_symlinks)?,
 
-            Self::Type(file_type) => {
-                let metadata = Some(resolve_metadata(dir_entry)?);
-                match file_type {
-                    FileType::Directory => metadata.as_ref().unwrap().is_dir(),
-                    FileType::File => metadata.as_ref().unwrap().is_file(),
-                }
-            }
+            Self::Type(file_type) => match file_type {
+                // checked on the entry itself, as resolving the metadata may follow symlinks
+                FileType::Symlink => dir_entry.file_type()?.is_symlink(),
+                FileType::BrokenSymlink => is_broken_symlink(dir_entry),
+                FileType::Directory => resolve_metadata(dir_entry, follow_symlinks)?.is_dir(),
+                FileType::File => resolve_metadata(dir_entry, follow_symlinks)?.is_file(),
+            },
             Self::Name(pattern) => pattern.is_match(dir_entry.file_name().to_str().unwrap()),
+            Self::Path(pattern) => pattern.is_match(&relative_path(&dir_entry.path(), root)),
+            Self::Age(age) => resolve_metadata(dir_entry, follow_symlinks)
+                .ok()
+                .and_then(|metadata| metadata.modified().ok())
+                .is_some_and(|time| age.matches(time)),
+            Self::AccessAge(age) => resolve_metadata(dir_entry, follow_symlinks)
+                .ok()
+                .and_then(|metadata| metadata.accessed().ok())
+                .is_some_and(|time| age.matches(time)),
+            Self::NameLength { min, max } => {
+                // count characters rather than bytes so multibyte names aren't over-counted
+                let length = dir_entry.file_name().to_string_lossy().chars().count();
+                min.is_none_or(|min| length >= min) && max.is_none_or(|max| length <= max)
+            }
+            Self::Empty => is_empty(dir_entry, follow_symlinks),
+            Self::Mime(mime_types) => sniff_mime_type(dir_entry, follow_symlinks)
+                .is_some_and(|mime_type| mime_types.iter().any(|m| mime_matches(m, mime_type))),
+            #[cfg(unix)]
+            Self::Mode { mode, mask } => {
+                resolve_metadata(dir_entry, follow_symlinks).is_ok_and(|metadata| {
+                    use std::os::unix::fs::PermissionsExt;
+                    metadata.permissions().mode() & mask == *mode
+                })
+            }
+            #[cfg(unix)]
+            Self::Owner(uid) => {
+                resolve_metadata(dir_entry, follow_symlinks).is_ok_and(|metadata| {
+                    use std::os::unix::fs::MetadataExt;
+                    metadata.uid() == *uid
+                })
+            }
+            #[cfg(unix)]
+            Self::Group(gid) => {
+                resolve_metadata(dir_entry, follow_symlinks).is_ok_and(|metadata| {
+                    use std::os::unix::fs::MetadataExt;
+                    metadata.gid() == *gid
+                })
+            }
         };
+        if !matches!(self, Self::None | Self::MergeAnd(_) | Self::MergeOr(_)) {
+            tracing::trace!(
+                "{} {} {self:?}",
+                dir_entry.path().to_string_lossy(),
+                if res { "matches" } else { "doesn't match" }
+            );
+        }
         Ok(res)
     }
 }
 
-/// Returns a dir entry's file metadata after following symlinks
-pub fn resolve_metadata(dir_entry: &fs::DirEntry) -> anyhow::Result<fs::Metadata> {
+impl FileAge {
+    /// Checks if a point in time satisfies this age constraint
+    pub fn matches(&self, time: SystemTime) -> bool {
+        // times in the future are considered brand new
+        let age = SystemTime::now()
+            .duration_since(time)
+            .unwrap_or(Duration::ZERO);
+        match self {
+            Self::Within(max) => age <= *max,
+            Self::OlderThan(min) => age > *min,
+        }
+    }
+}
+
+/// Checks if a file is empty or a directory has no entries.
+///
+/// Entries that can't be read (e.g. because of permissions) are not considered empty.
+fn is_empty(dir_entry: &fs::DirEntry, follow_symlinks: bool) -> bool {
+    match resolve_metadata(dir_entry, follow_symlinks) {
+        Ok(metadata) if metadata.is_dir() => {
+            fs::read_dir(dir_entry.path()).is_ok_and(|mut entries| entries.next().is_none())
+        }
+        Ok(metadata) => metadata.len() == 0,
+        Err(_) => false,
+    }
+}
+
+/// Guesses the content type of a file from its first bytes.
+///
+/// Directories, unreadable files and unknown types give `None`.
+fn sniff_mime_type(dir_entry: &fs::DirEntry, follow_symlinks: bool) -> Option<&'static str> {
+    if !resolve_metadata(dir_entry, follow_symlinks).is_ok_and(|metadata| metadata.is_file()) {
+        return None;
+    }
+    infer::get_from_path(dir_entry.path())
+        .ok()
+        .flatten()
+        .map(|kind| kind.mime_type())
+}
+
+/// Checks if a MIME type matches a configured one, which may be a `category/*` wildcard
+fn mime_matches(pattern: &str, mime_type: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(category) => mime_type
+            .split_once('/')
+            .is_some_and(|(mime_category, _)| mime_category.eq_ignore_ascii_case(category)),
+        None => mime_type.eq_ignore_ascii_case(pattern),
+    }
+}
+
+/// Returns `path` relative to `root` with `/` separators, or all of it if it isn't under `root`
+fn relative_path(path: &Path, root: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether reading files under `path` updates their access time. Only filesystems mounted
+/// with `noatime` on Linux are known not to
+#[cfg(target_os = "linux")]
+pub fn records_access_times(path: &Path) -> bool {
+    use rustix::fs::{statvfs, StatVfsMountFlags};
+    statvfs(path).map_or(true, |stats| {
+        !stats.f_flag.contains(StatVfsMountFlags::NOATIME)
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn records_access_times(_path: &Path) -> bool {
+    true
+}
+
+/// Returns a dir entry's file metadata, after following symlinks with `follow_symlinks`.
+/// Broken symlinks give the metadata of the link itself.
+pub fn resolve_metadata(
+    dir_entry: &fs::DirEntry,
+    follow_symlinks: bool,
+) -> anyhow::Result<fs::Metadata> {
     let symlink = dir_entry.file_type()?.is_symlink();
-    if symlink {
-        Ok(fs::metadata(dir_entry.path())?)
+    if symlink && follow_symlinks {
+        match fs::metadata(dir_entry.path()) {
+            // nothing to follow, the link itself is all there is
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(dir_entry.metadata()?),
+            res => Ok(res?),
+        }
     } else {
         Ok(dir_entry.metadata()?)
     }
 }
 
-/// Compiles a list of configuration match rules into a [`FileMatchRule`] for efficient checks
+/// Checks if an entry is a symlink whose target doesn't exist
+pub fn is_broken_symlink(dir_entry: &fs::DirEntry) -> bool {
+    dir_entry.file_type().is_ok_and(|ft| ft.is_symlink())
+        && fs::metadata(dir_entry.path()).is_err_and(|err| err.kind() == io::ErrorKind::NotFound)
+}
+
+/// Compiles a list of configuration match rules into a [`FileMatchRule`] for efficient checks.
+///
+/// When `case_insensitive` is set, filename based rules (names, extensions, patterns
+/// and globs) ignore case.
 #[allow(clippy::module_name_repetitions)]
-pub fn compile_config_rules(rules: &Vec<config::MatchRule>) -> anyhow::Result<FileMatchRule> {
+pub fn compile_config_rules(
+    rules: &Vec<config::MatchRule>,
+    case_insensitive: bool,
+) -> anyhow::Result<FileMatchRule> {
     if rules.is_empty() {
         // empty rules, meaning no file can be valid
         return Ok(FileMatchRule::None);
     }
 
     let mut merge_rules = Vec::new();
-    if let Some(pattern) = compile_filename_pattern(rules)? {
+    if let Some(pattern) = compile_filename_pattern(rules, case_insensitive)? {
         merge_rules.push(FileMatchRule::Name(pattern));
     }
+    if let Some(pattern) = compile_path_pattern(rules, case_insensitive)? {
+        merge_rules.push(FileMatchRule::Path(pattern));
+    }
+    let mime_types = rules
+        .iter()
+        .filter_map(|rule| match rule {
+            config::MatchRule::Mime { mime } => Some(mime.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !mime_types.is_empty() {
+        merge_rules.push(FileMatchRule::Mime(mime_types));
+    }
+    for rule in rules {
+        match rule {
+            config::MatchRule::ModifiedWithin { days } => {
+                merge_rules.push(FileMatchRule::Age(FileAge::Within(days_duration(*days))));
+            }
+            config::MatchRule::ModifiedOlderThan { days } => {
+                merge_rules.push(FileMatchRule::Age(FileAge::OlderThan(days_duration(*days))));
+            }
+            config::MatchRule::AccessedOlderThan { days } => {
+                let age = FileAge::OlderThan(days_duration(*days));
+                merge_rules.push(FileMatchRule::AccessAge(age));
+            }
+            config::MatchRule::NameLength { length } => {
+                merge_rules.push(FileMatchRule::NameLength {
+                    min: length.min,
+                    max: length.max,
+                });
+            }
+            config::MatchRule::Type { file_type } => {
+                merge_rules.push(FileMatchRule::Type(*file_type));
+            }
+            config::MatchRule::Not { not } => {
+                // an empty `not` list compiles to `FileMatchRule::None`,
+                // so its negation lets every file match
+                let nested = compile_config_rules(not, case_insensitive)?;
+                merge_rules.push(FileMatchRule::Not(Box::new(nested)));
+            }
+            config::MatchRule::All { all } => {
+                // each rule is compiled on its own, as compiling them together would OR them
+                let nested = all
+                    .iter()
+                    .map(|rule| compile_config_rules(&vec![rule.clone()], case_insensitive))
+                    .collect::<anyhow::Result<_>>()?;
+                merge_rules.push(FileMatchRule::MergeAnd(nested));
+            }
+            config::MatchRule::Empty { empty: true } => merge_rules.push(FileMatchRule::Empty),
+            config::MatchRule::Empty { empty: false } => {
+                merge_rules.push(FileMatchRule::Not(Box::new(FileMatchRule::Empty)));
+            }
+            #[cfg(unix)]
+            config::MatchRule::Mode { mode, mask } => merge_rules.push(FileMatchRule::Mode {
+                mode: *mode,
+                mask: mask.unwrap_or(*mode),
+            }),
+            #[cfg(not(unix))]
+            config::MatchRule::Mode { mode, .. } => {
+                tracing::warn!(
+                    "mode rules only work on Unix, {{ mode = {mode:#o} }} will never match"
+                );
+            }
+            #[cfg(unix)]
+            config::MatchRule::Owner { uid } => {
+                merge_rules.push(FileMatchRule::Owner(resolve_uid(uid)?));
+            }
+            #[cfg(unix)]
+            config::MatchRule::Group { gid } => {
+                merge_rules.push(FileMatchRule::Group(resolve_gid(gid)?));
+            }
+            #[cfg(not(unix))]
+            config::MatchRule::Owner { .. } | config::MatchRule::Group { .. } => {
+                tracing::warn!("owner rules only work on Unix, uid and gid rules will never match");
+            }
+            _ => {}
+        }
+    }
 
     let merged = if merge_rules.is_empty() {
         FileMatchRule::None
@@ -106,7 +418,10 @@ pub fn compile_config_rules(rules: &Vec<config::MatchRule>) -> anyhow::Result<Fi
     Ok(merged)
 }
 
-fn compile_filename_pattern(rules: &Vec<config::MatchRule>) -> anyhow::Result<Option<RegexSet>> {
+fn compile_filename_pattern(
+    rules: &Vec<config::MatchRule>,
+    case_insensitive: bool,
+) -> anyhow::Result<Option<RegexSet>> {
     let mut names = <Vec<&str>>::new();
     let mut extensions = <Vec<&str>>::new();
     let mut raw_patterns = Vec::new();
@@ -116,6 +431,15 @@ fn compile_filename_pattern(rules: &Vec<config::MatchRule>) -> anyhow::Result<Op
             config::MatchRule::Name { name } => names.push(name),
             config::MatchRule::Extension { ext } => extensions.push(ext),
             config::MatchRule::Pattern { pattern } => raw_patterns.push(pattern.clone()),
+            config::MatchRule::Glob { glob } => {
+                let pattern = glob_to_regex(glob);
+                // ranges may still be invalid, e.g. `[z-a]`
+                if let Err(err) = regex::Regex::new(&pattern) {
+                    anyhow::bail!("Invalid glob {glob:?}: {err}");
+                }
+                raw_patterns.push(pattern);
+            }
+            _ => {}
         }
     }
 
@@ -126,7 +450,7 @@ fn compile_filename_pattern(rules: &Vec<config::MatchRule>) -> anyhow::Result<Op
             .map(regex::escape)
             .collect::<Vec<_>>()
             .join("|");
-        patterns.push(format!("^({})$", match_pat));
+        patterns.push(format!("^({match_pat})$"));
     }
     if !extensions.is_empty() {
         let match_pat = extensions
@@ -134,7 +458,9 @@ fn compile_filename_pattern(rules: &Vec<config::MatchRule>) -> anyhow::Result<Op
             .map(regex::escape)
             .collect::<Vec<_>>()
             .join("|");
-        patterns.push(format!("\\.({})$", match_pat));
+        // extensions may have several parts (`tar.gz`), and need a non-empty name
+        // before them so that hidden files like `.gitignore` have no extension
+        patterns.push(format!("^.+\\.({match_pat})$"));
     }
     patterns.extend(raw_patterns);
 
@@ -143,8 +469,188 @@ fn compile_filename_pattern(rules: &Vec<config::MatchRule>) -> anyhow::Result<Op
     }
     let filename_pattern = RegexSetBuilder::new(patterns)
         .unicode(true)
-        .case_insensitive(false)
+        .case_insensitive(case_insensitive)
         .multi_line(false)
         .build()?;
     Ok(Some(filename_pattern))
 }
+
+fn compile_path_pattern(
+    rules: &[config::MatchRule],
+    case_insensitive: bool,
+) -> anyhow::Result<Option<RegexSet>> {
+    let patterns = rules
+        .iter()
+        .filter_map(|rule| match rule {
+            config::MatchRule::PathPattern { path_pattern } => Some(path_pattern),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let path_pattern = RegexSetBuilder::new(patterns)
+        .unicode(true)
+        .case_insensitive(case_insensitive)
+        .build()?;
+    Ok(Some(path_pattern))
+}
+
+/// Translates a filename glob into an anchored regex.
+///
+/// Supports `*`, `?` and `[...]` character classes (negated with `[!...]`). A `]` right
+/// after the opening bracket is part of the class, and an unterminated class is literal.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                let negated = chars.next_if_eq(&'!').is_some();
+                let mut members = Vec::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == ']' && !members.is_empty() {
+                        closed = true;
+                        break;
+                    }
+                    members.push(c);
+                }
+                if closed {
+                    regex.push('[');
+                    if negated {
+                        regex.push('^');
+                    }
+                    push_class_members(&mut regex, &members);
+                    regex.push(']');
+                } else {
+                    // unterminated class, treat it literally
+                    let literal = members.iter().collect::<String>();
+                    let bang = if negated { "!" } else { "" };
+                    regex.push_str(&regex::escape(&format!("[{bang}{literal}")));
+                }
+            }
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// Writes the members of a glob class, keeping `a-z` ranges and escaping everything
+/// the regex syntax gives a meaning to within classes (e.g. `&&` intersections)
+fn push_class_members(regex: &mut String, members: &[char]) {
+    let push = |regex: &mut String, c: char| {
+        if matches!(c, '\\' | '[' | ']' | '&' | '~' | '-' | '^') {
+            regex.push('\\');
+        }
+        regex.push(c);
+    };
+    let mut i = 0;
+    while i < members.len() {
+        if i + 2 < members.len() && members[i + 1] == '-' {
+            push(regex, members[i]);
+            regex.push('-');
+            push(regex, members[i + 2]);
+            i += 3;
+        } else {
+            push(regex, members[i]);
+            i += 1;
+        }
+    }
+}
+
+/// Returns the uid of a user given by id or name
+#[cfg(unix)]
+fn resolve_uid(user: &config::UnixId) -> anyhow::Result<u32> {
+    match user {
+        config::UnixId::Id(uid) => Ok(*uid),
+        config::UnixId::Name(name) => users::get_user_by_name(name)
+            .map(|user| user.uid())
+            .ok_or_else(|| anyhow::format_err!("Unknown user '{name}'")),
+    }
+}
+
+/// Returns the gid of a group given by id or name
+#[cfg(unix)]
+fn resolve_gid(group: &config::UnixId) -> anyhow::Result<u32> {
+    match group {
+        config::UnixId::Id(gid) => Ok(*gid),
+        config::UnixId::Name(name) => users::get_group_by_name(name)
+            .map(|group| group.gid())
+            .ok_or_else(|| anyhow::format_err!("Unknown group '{name}'")),
+    }
+}
+
+/// Converts a number of days from the config, huge ones standing for "forever"
+fn days_duration(days: u64) -> Duration {
+    Duration::from_secs(days.saturating_mul(24 * 60 * 60))
+}
+
+#[test]
+fn test_extension_pattern() {
+    let rules = vec![
+        config::MatchRule::Extension {
+            ext: "tar.gz".to_string(),
+        },
+        config::MatchRule::Extension {
+            ext: "gitignore".to_string(),
+        },
+    ];
+    let pattern = compile_filename_pattern(&rules, false).unwrap().unwrap();
+    assert!(pattern.is_match("archive.tar.gz"));
+    assert!(!pattern.is_match("archive.gz"));
+    assert!(!pattern.is_match(".tar.gz"));
+    assert!(!pattern.is_match(".gitignore"));
+    assert!(pattern.is_match("backup.gitignore"));
+}
+
+#[test]
+fn test_days_duration() {
+    assert_eq!(Duration::from_hours(48), days_duration(2));
+    assert_eq!(Duration::from_secs(u64::MAX), days_duration(u64::MAX));
+    assert!(!FileAge::OlderThan(days_duration(u64::MAX)).matches(SystemTime::UNIX_EPOCH));
+}
+
+#[test]
+fn test_uses_access_time() {
+    let compile = |toml: &str| {
+        let rules: toml::Value = toml::from_str(&format!("rules = [{toml}]")).unwrap();
+        let rules = rules["rules"].clone().try_into().unwrap();
+        compile_config_rules(&rules, false).unwrap()
+    };
+    assert!(compile("{ not = [{ accessed-older-than = 3 }] }").uses_access_time());
+    assert!(!compile("{ modified-older-than = 3 }, { ext = \"txt\" }").uses_access_time());
+}
+
+#[test]
+fn test_mime_matches() {
+    assert!(mime_matches("image/*", "image/png"));
+    assert!(mime_matches("image/png", "image/png"));
+    assert!(!mime_matches("image/jpeg", "image/png"));
+    assert!(!mime_matches("image/*", "video/mp4"));
+}
+
+#[test]
+fn test_glob_to_regex() {
+    assert_eq!("^.*\\.log$", glob_to_regex("*.log"));
+    assert_eq!("^report\\-..\\.txt$", glob_to_regex("report-??.txt"));
+    assert_eq!("^img[^0-9]$", glob_to_regex("img[!0-9]"));
+    assert_eq!("^a\\[b$", glob_to_regex("a[b"));
+    assert_eq!("^a\\[!b\\\\$", glob_to_regex("a[!b\\"));
+    assert_eq!("^[\\]a]$", glob_to_regex("[]a]"));
+    assert_eq!("^\\[\\]$", glob_to_regex("[]"));
+    assert_eq!("^x[a\\&\\&b]$", glob_to_regex("x[a&&b]"));
+    assert_eq!("^[\\-a\\~\\~\\-]$", glob_to_regex("[-a~~-]"));
+    assert_eq!("^[^\\^a-c]$", glob_to_regex("[!^a-c]"));
+    for glob in ["[]a]", "[]", "x[a&&b]", "[-a~~-]", "[!^a-c]"] {
+        assert!(regex::Regex::new(&glob_to_regex(glob)).is_ok(), "{glob}");
+    }
+
+    let rules: toml::Value = toml::from_str("rules = [{ glob = \"[z-a]\" }]").unwrap();
+    let rules = rules["rules"].clone().try_into().unwrap();
+    let err = compile_config_rules(&rules, false).unwrap_err();
+    assert!(err.to_string().starts_with("Invalid glob \"[z-a]\""));
+}
@@ -0,0 +1,148 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::Hasher;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use twox_hash::XxHash3_64;
+
+use crate::checker::IssueReason;
+
+/// Directories modified more recently than this are not cached, since a change made
+/// within the timestamp resolution of the filesystem would not change their mtime
+const RACY_DELAY: Duration = Duration::from_secs(2);
+
+/// Results of previous directory scans, stored across runs.
+///
+/// A directory's entries are only read again when its modification time changes,
+/// which happens when entries are added, removed or renamed. Changes to the files
+/// themselves are not noticed.
+#[derive(Debug, Default)]
+pub struct ScanCache {
+    path: PathBuf,
+    dirs: HashMap<PathBuf, CachedDir>,
+    /// Directories scanned or read from the cache during this run
+    used: HashSet<PathBuf>,
+    /// Roots of the directories checked during this run
+    roots: Vec<PathBuf>,
+}
+
+/// Cached scan of a single directory, without its children
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedDir {
+    /// Modification time of the directory, in nanoseconds since the Unix epoch
+    mtime: u64,
+    /// Hash of the checker config and of the ignore files that applied to the directory
+    stamp: u64,
+    /// Misplaced entries of the directory
+    pub issues: Vec<(PathBuf, IssueReason)>,
+    /// Children directories that were descended into
+    pub subdirs: Vec<PathBuf>,
+}
+
+impl ScanCache {
+    /// Loads the cache stored in the config directory, starting afresh if it is unreadable
+    pub fn load(config_dir: &Path) -> Self {
+        let path = config_dir.join("cache").join("scan.json");
+        let dirs = fs::read(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_slice(&contents).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            dirs,
+            ..Self::default()
+        }
+    }
+
+    /// Writes the cache back, dropping the directories under checked roots that weren't seen
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        let (used, roots) = (&self.used, &self.roots);
+        self.dirs.retain(|path, _| {
+            used.contains(path) || !roots.iter().any(|root| path.starts_with(root))
+        });
+        self.used.clear();
+        self.roots.clear();
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_vec(&self.dirs)?)
+            .with_context(|| format!("Could not write scan cache {}", self.path.to_string_lossy()))
+    }
+
+    /// Marks a directory as checked, so that stale entries below it get dropped
+    pub fn add_root(&mut self, root: &Path) {
+        self.roots.push(root.to_path_buf());
+    }
+
+    /// Returns the cached scan of a directory if it is still up to date
+    pub fn get(&mut self, path: &Path, stamp: u64) -> Option<&CachedDir> {
+        let mtime = mtime(path).ok()?;
+        let cached = self.dirs.get(path)?;
+        if cached.mtime != mtime || cached.stamp != stamp {
+            return None;
+        }
+        self.used.insert(path.to_path_buf());
+        Some(cached)
+    }
+
+    /// Stores the scan of a directory, unless it was modified too recently to be trusted
+    pub fn insert(
+        &mut self,
+        path: &Path,
+        stamp: u64,
+        issues: Vec<(PathBuf, IssueReason)>,
+        subdirs: Vec<PathBuf>,
+    ) {
+        let Ok(mtime) = mtime(path) else {
+            return;
+        };
+        let racy = SystemTime::now()
+            .checked_sub(RACY_DELAY)
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .is_none_or(|limit| u128::from(mtime) >= limit.as_nanos());
+        if racy {
+            self.dirs.remove(path);
+            return;
+        }
+        self.used.insert(path.to_path_buf());
+        self.dirs.insert(
+            path.to_path_buf(),
+            CachedDir {
+                mtime,
+                stamp,
+                issues,
+                subdirs,
+            },
+        );
+    }
+}
+
+/// Combines a parent stamp with the modification times of files in a directory,
+/// so that editing one of them invalidates the cache
+pub fn stamp(parent: u64, dir: &Path, filenames: &[&str]) -> u64 {
+    let mut hasher = XxHash3_64::new();
+    hasher.write_u64(parent);
+    for filename in filenames {
+        hasher.write_u64(mtime(&dir.join(filename)).unwrap_or(0));
+    }
+    hasher.finish()
+}
+
+/// Hashes a value's debug representation, used to tell apart checker configs
+pub fn fingerprint(value: &impl std::fmt::Debug) -> u64 {
+    XxHash3_64::oneshot(format!("{value:?}").as_bytes())
+}
+
+/// Returns the modification time of a file, in nanoseconds since the Unix epoch
+fn mtime(path: &Path) -> io::Result<u64> {
+    let modified = fs::metadata(path)?.modified()?;
+    let since_epoch = modified
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    u64::try_from(since_epoch.as_nanos())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}